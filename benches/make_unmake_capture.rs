@@ -22,7 +22,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("make_unmake_capture", |b| {
         b.iter(|| {
             boards.make_ply(&ply);
-            boards.unmake_ply(&ply, None);
+            boards.unmake_ply(&ply);
         })
     });
 }
@@ -21,7 +21,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("make_unmake_no_capture", |b| {
         b.iter(|| {
             boards.make_ply(&ply);
-            boards.unmake_ply(&ply, None);
+            boards.unmake_ply(&ply);
         })
     });
 }
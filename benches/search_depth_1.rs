@@ -1,4 +1,4 @@
-use balatro_chess::chess_engine;
+use balatro_chess::chess_engine::{self, bitboard::SearchLimits};
 use criterion::{Criterion, criterion_group, criterion_main};
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -16,7 +16,12 @@ fn criterion_benchmark(c: &mut Criterion) {
     );
     c.bench_function("search depth 1", |b| {
         b.iter(|| {
-            boards.search_next_ply(None, 1, Default::default());
+            boards.transposition_table.clear();
+            boards.search_next_ply(
+                chess_engine::pieces::PieceColor::White,
+                &SearchLimits::depth(1),
+                Default::default(),
+            );
         })
     });
 }
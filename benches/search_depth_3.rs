@@ -1,4 +1,7 @@
-use balatro_chess::chess_engine;
+use balatro_chess::chess_engine::{
+    self,
+    bitboard::{ClassicalEvaluator, SearchConfig, SearchLimits},
+};
 use criterion::{Criterion, criterion_group, criterion_main};
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -16,7 +19,24 @@ fn criterion_benchmark(c: &mut Criterion) {
     );
     c.bench_function("search depth 3", |b| {
         b.iter(|| {
-            boards.search_next_ply(None, 3, Default::default());
+            boards.transposition_table.clear();
+            boards.search_next_ply(
+                chess_engine::pieces::PieceColor::White,
+                &SearchLimits::depth(3),
+                Default::default(),
+            );
+        })
+    });
+    c.bench_function("search depth 3 without selectivity", |b| {
+        let limits = SearchLimits::depth(3).with_config(SearchConfig::PLAIN);
+        b.iter(|| {
+            boards.transposition_table.clear();
+            boards.search_with_limits(
+                chess_engine::pieces::PieceColor::White,
+                &limits,
+                ClassicalEvaluator::default(),
+                &mut |_| {},
+            );
         })
     });
 }
@@ -1,5 +1,8 @@
-use balatro_chess::chess_engine::bitboard::Bitboards;
-use criterion::{Criterion, criterion_group, criterion_main};
+use balatro_chess::chess_engine::{
+    bitboard::{BitIndex, Bitboard, Bitboards, SearchLimits},
+    pieces::PieceColor,
+};
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
 
 fn criterion_benchmark(c: &mut Criterion) {
     let mut boards = Bitboards::new_from_str(
@@ -13,7 +16,26 @@ fn criterion_benchmark(c: &mut Criterion) {
     );
     c.bench_function("stepping_pieces", |b| {
         b.iter(|| {
-            boards.search_next_ply(None, 1, Default::default());
+            boards.search_next_ply(
+                PieceColor::White,
+                &SearchLimits::depth(1),
+                Default::default(),
+            );
+        })
+    });
+
+    let tiles: Vec<Bitboard> = (0..256u32)
+        .map(|tile| BitIndex::from(tile).into())
+        .collect();
+    let blocked = boards.blocked_mask_for_color(PieceColor::White);
+    let capturable = boards.all_pieces_by_color(PieceColor::Black);
+    c.bench_function("stepping_attack_masks", |b| {
+        b.iter(|| {
+            for tile in tiles.iter() {
+                black_box(tile.knight_move_mask(&blocked, &capturable));
+                black_box(tile.king_move_mask(&blocked, &capturable));
+                black_box(tile.pawn_en_prise_mask(&blocked, PieceColor::White));
+            }
         })
     });
 }
@@ -0,0 +1,77 @@
+use std::{process::ExitCode, thread::available_parallelism};
+
+use balatro_chess::chess_engine::bitboard::{Tuner, Weights};
+
+/// Tuning passes when none are given
+const DEFAULT_PASSES: usize = 100;
+
+/// `tune <positions> <output> [passes] [start weights]`
+/// Tunes the weights on a file of labelled positions, one `<fen> <result>` per line, and writes
+/// them to `output`, which the engine loads with the `WeightsFile` UCI option
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [positions_path, output_path, rest @ ..] = args.as_slice() else {
+        eprintln!("usage: tune <positions> <output> [passes] [start weights]");
+        return ExitCode::FAILURE;
+    };
+    let passes = match rest.first().map(|passes| passes.parse()) {
+        None => DEFAULT_PASSES,
+        Some(Ok(passes)) => passes,
+        Some(Err(err)) => {
+            eprintln!("invalid pass count: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let weights = match rest.get(1).map(std::fs::read_to_string) {
+        None => Weights::default(),
+        Some(Ok(input)) => match input.parse() {
+            Ok(weights) => weights,
+            Err(err) => {
+                eprintln!("invalid start weights: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+        Some(Err(err)) => {
+            eprintln!("failed to read start weights: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let input = match std::fs::read_to_string(positions_path) {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("failed to read positions: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let threads = available_parallelism().map_or(1, |threads| threads.get());
+    let mut tuner = match Tuner::from_labelled(&input, threads) {
+        Ok(tuner) => tuner,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!(
+        "tuning on {} positions with {} threads",
+        tuner.positions().len(),
+        threads
+    );
+
+    // Weights are written after every pass, so an interrupted run keeps its progress
+    let mut written = Ok(());
+    let tuned = tuner.tune(weights, passes, &mut |report| {
+        eprintln!(
+            "pass {}: error {:.6}, {} weights changed",
+            report.pass, report.error, report.changed
+        );
+        written = std::fs::write(output_path, report.weights.to_string());
+    });
+    match written.and_then(|()| std::fs::write(output_path, tuned.to_string())) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("failed to write weights: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
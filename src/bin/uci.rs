@@ -0,0 +1,30 @@
+use std::{
+    io::{BufRead, Write},
+    sync::mpsc::channel,
+};
+
+use balatro_chess::chess_engine::uci::UciEngine;
+
+fn main() {
+    let (sender, receiver) = channel::<String>();
+
+    // Responses are printed from their own thread, so search info arrives while reading input
+    let printer = std::thread::spawn(move || {
+        let mut stdout = std::io::stdout();
+        for line in receiver {
+            let _ = writeln!(stdout, "{}", line);
+            let _ = stdout.flush();
+        }
+    });
+
+    let mut engine = UciEngine::new(sender);
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if !engine.handle_command(&line) {
+            break;
+        }
+    }
+
+    drop(engine);
+    let _ = printer.join();
+}
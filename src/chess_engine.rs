@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 
 mod game;
-pub use game::Game;
+pub use game::{Game, GameError, GameResult, PgnError, search_comment};
 
 pub mod moves;
 pub mod pieces;
@@ -12,6 +12,8 @@ use debug::ChessDebugPlugin;
 pub mod bitboard;
 mod zobrist;
 
+pub mod uci;
+
 pub struct ChessEnginePlugin;
 impl Plugin for ChessEnginePlugin {
     fn build(&self, app: &mut App) {
@@ -1,29 +1,40 @@
 use bevy::prelude::*;
 use ethnum::u256;
-use move_gen::ply::{captures_only, legality_filter};
-use simplehash::FnvHasher64;
-use std::{
-    collections::HashMap,
-    fmt::Display,
-    hash::BuildHasherDefault,
-    sync::{Arc, Mutex},
-};
+use move_gen::ply::legality_filter;
+use std::{fmt::Display, sync::Arc};
 use strum::IntoEnumIterator;
 
 use super::{
     pieces::{
         PIECE_COMBO_COUNT, PIECE_TYPE_COUNT, Piece, PieceColor, PieceType, PieceWithBitboard,
     },
-    zobrist::{Zobrist, ZobristHash},
+    zobrist::{CHANGE_PLAYER_INDEX, Zobrist, ZobristHash},
 };
 
+/// Slots of the en prise cache
+const EN_PRISE_TABLE_SIZE: usize = 1 << 14;
+
 pub mod bitwise_traits;
 pub mod move_gen;
 
+mod fen;
+mod lockless;
+pub use fen::{FenError, PositionInfo, STARTING_FEN};
+use lockless::LocklessTable;
+
+mod notation;
+mod perft;
+
 mod search;
-pub use search::Weights;
+use search::PawnTable;
+pub use search::{
+    Bound, ClassicalEvaluator, Clock, Evaluator, IterationReport, LabelError, LabelledPosition,
+    MATE_SCORE, MAX_DEPTH, PlyKey, SEARCH_STACK_SIZE, SearchConfig, SearchLimits, SquareWeights,
+    StopFlag, TranspositionEntry, TranspositionTable, Tuner, TuningReport, Weights, WeightsError,
+    mate_distance,
+};
 
-pub use move_gen::ply::Ply;
+pub use move_gen::{move_list::MoveList, ply::Ply};
 
 /// u32 based position on the Bitboard. Derived by couting `trailing_zeros`
 #[derive(Clone, Debug, Default, Deref, DerefMut, PartialEq, Eq, Copy)]
@@ -99,14 +110,14 @@ impl From<u256> for Bitboard {
 impl Bitboard {
     #[inline]
     pub fn set(&mut self, index: BitIndex, value: bool) {
-        *self &= !(1 << *index);
-        *self |= (value as u128) << *index;
+        **self &= !(u256::ONE << *index);
+        **self |= u256::from(value as u8) << *index;
     }
 
     #[allow(dead_code)]
     #[inline]
     pub fn get<T: std::ops::Deref<Target = u32>>(&self, index: T) -> bool {
-        **self & (1 << *index) != 0
+        **self & (u256::ONE << *index) != 0
     }
 
     /// Gets the position for the
@@ -130,6 +141,17 @@ impl Bitboard {
     }
 }
 
+/// State of a position which can't be derived from a ply when unmaking it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct UnmakeState {
+    unmoved_pieces: Bitboard,
+    en_passant: Bitboard,
+    /// Piece list positions the captured piece and the promoting pawn were removed from,
+    /// so unmaking restores the lists in their exact order
+    captured_slot: usize,
+    promoted_slot: usize,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Bitboards {
     /// index = PieceType + (PieceColor * amount of PieceType)
@@ -141,23 +163,41 @@ pub struct Bitboards {
     /// mask of all pieces in their initial position.
     /// updated on moves or captures
     unmoved_pieces: Bitboard,
+    /// previous states, restored when unmaking plys
+    state_history: Vec<UnmakeState>,
     /// Board of en passant vulnerable positions
     en_passant: Bitboard,
+    /// Color whose turn it is, toggled by making and unmaking plys of either color
+    side_to_move: PieceColor,
 
     // Zobrist hashing
     pub zobrist_table: Arc<Zobrist>,
     pub zobrist_hash: ZobristHash,
+    /// Hash of the pawns only, keying the pawn structure cache
+    pub pawn_hash: ZobristHash,
 
-    //`FnvHasher64` has proven to be the most efficient in testing for these HashMaps
-    /// thricefold repetition protection.
-    pub visited_positions: Arc<Mutex<HashMap<u32, isize, BuildHasherDefault<FnvHasher64>>>>,
+    /// Hashes of the positions since the last irreversible ply, used for threefold repetition
+    /// detection. Flagged entries were reached by an irreversible ply and end the lookup.
+    /// Owned by each clone, so searches never contend over it.
+    position_history: Vec<(ZobristHash, bool)>,
 
     // Storing
-    pub check_quiescence_table: bool,
-    pub quiescence_table: Arc<Mutex<HashMap<(u32, u16, u8), i32, BuildHasherDefault<FnvHasher64>>>>,
-    pub pv_table: Arc<Mutex<HashMap<(u32, u16), Ply, BuildHasherDefault<FnvHasher64>>>>,
-    //pub evaluation_table: Arc<Mutex<HashMap<u32, i32, BuildHasherDefault<FnvHasher64>>>>,
-    pub en_prise_table: Arc<Mutex<HashMap<(u32, u8), Bitboard, BuildHasherDefault<FnvHasher64>>>>,
+    /// Shared between clones, so consecutive and parallel searches of a game reuse it
+    pub transposition_table: Arc<TranspositionTable>,
+    /// Direct mapped cache of `en_prise_by_color`, allocated on first use
+    en_prise_table: Arc<EnPriseTable>,
+    /// Pawn structures by pawn hash, allocated on first use
+    pawn_table: Arc<PawnTable>,
+}
+
+/// Lockless cache of en prise masks, keyed by position hash and color
+#[derive(Debug)]
+struct EnPriseTable(LocklessTable<4>);
+
+impl Default for EnPriseTable {
+    fn default() -> Self {
+        Self(LocklessTable::with_capacity(EN_PRISE_TABLE_SIZE))
+    }
 }
 
 impl PartialEq for Bitboards {
@@ -176,7 +216,7 @@ impl Display for Bitboards {
 
         for (i, piece) in mailbox.iter().enumerate() {
             let rank = (b'1' + (i as u32 / self.limits.trailing_ones()) as u8) as char;
-            if i as u32 % self.limits.trailing_ones() == 0 {
+            if (i as u32).is_multiple_of(self.limits.trailing_ones()) {
                 board_str.push('\n');
                 board_str.push(rank);
             }
@@ -234,6 +274,25 @@ impl Bitboards {
 
         let unmoved_pieces = boards.iter().fold(Bitboard(u256::ZERO), |acc, e| acc | *e);
 
+        Self::from_placement(
+            boards,
+            piece_list,
+            limits,
+            unmoved_pieces,
+            Bitboard(u256::ZERO),
+            PieceColor::White,
+        )
+    }
+
+    /// Assembles a position from its parts, generating the initial hash
+    fn from_placement(
+        boards: [Bitboard; PIECE_COMBO_COUNT],
+        piece_list: Vec<Vec<BitIndex>>,
+        limits: Bitboard,
+        unmoved_pieces: Bitboard,
+        en_passant: Bitboard,
+        side_to_move: PieceColor,
+    ) -> Self {
         let zobrist_table = Arc::new(Zobrist::new());
 
         let mut new_bitboards = Self {
@@ -241,22 +300,72 @@ impl Bitboards {
             piece_list,
             limits,
             unmoved_pieces,
+            en_passant,
+            side_to_move,
             zobrist_table,
             ..Default::default()
         };
 
-        let zobrist_hash = new_bitboards
-            .zobrist_table
-            .gen_initial_hash_bitboard(new_bitboards.key_value_pieces_iter());
-        new_bitboards.zobrist_hash = zobrist_hash;
-        new_bitboards
-            .visited_positions
-            .lock()
-            .unwrap()
-            .insert(*zobrist_hash, 1);
+        new_bitboards.reset_hash();
         new_bitboards
     }
 
+    /// Hashes the position from scratch, making it the only visited position
+    fn reset_hash(&mut self) {
+        self.zobrist_hash = self.compute_hash();
+        self.pawn_hash = self.compute_pawn_hash();
+        self.position_history = vec![(self.zobrist_hash, true)];
+    }
+
+    /// Hash of the position computed from scratch, which `zobrist_hash` is incrementally kept equal to
+    pub fn compute_hash(&self) -> ZobristHash {
+        let mut hash = self
+            .zobrist_table
+            .gen_initial_hash_bitboard(self.key_value_pieces_iter());
+        hash ^= self
+            .zobrist_table
+            .state_hash(self.castling_pieces(), self.en_passant);
+        if self.side_to_move == PieceColor::Black {
+            hash ^= self.zobrist_table.table[CHANGE_PLAYER_INDEX];
+        }
+        hash
+    }
+
+    /// Hash of the pawns computed from scratch, which `pawn_hash` is incrementally kept equal to
+    pub fn compute_pawn_hash(&self) -> ZobristHash {
+        self.zobrist_table.gen_initial_hash_bitboard(
+            self.key_value_pieces_iter()
+                .filter(|(piece, _)| piece.0 == PieceType::Pawn),
+        )
+    }
+
+    /// Unmoved kings and rooks which may still castle, the only unmoved state not implied by the placement
+    fn castling_pieces(&self) -> Bitboard {
+        PieceColor::iter().fold(Bitboard(u256::ZERO), |acc, color| {
+            let king = self.boards[bitboard_idx(Piece(PieceType::King, color))];
+            if *king & *self.unmoved_pieces == 0 {
+                return acc;
+            }
+            let rooks = self.boards[bitboard_idx(Piece(PieceType::Rook, color))];
+            acc | ((king | rooks) & self.unmoved_pieces)
+        })
+    }
+
+    /// Panics if the incrementally updated hash drifted from the position
+    #[cfg(debug_assertions)]
+    fn verify_hash(&self) {
+        assert_eq!(
+            self.zobrist_hash,
+            self.compute_hash(),
+            "zobrist hash drifted from the position"
+        );
+        assert_eq!(
+            self.pawn_hash,
+            self.compute_pawn_hash(),
+            "pawn hash drifted from the pawns"
+        );
+    }
+
     pub fn to_mailbox(&self) -> Vec<Option<Piece>> {
         let tile_count = self.limits.count_ones() as usize;
         let mut mailbox = vec![None; tile_count];
@@ -273,6 +382,16 @@ impl Bitboards {
         mailbox
     }
 
+    /// Mask of active tiles
+    pub fn limits(&self) -> Bitboard {
+        self.limits
+    }
+
+    /// Piece standing on a given tile
+    pub fn piece_at(&self, idx: BitIndex) -> Option<Piece> {
+        Piece::iter().find(|piece| self.boards[bitboard_idx(*piece)].get(idx))
+    }
+
     pub fn key_value_pieces_iter(&self) -> impl Iterator<Item = (Piece, BitIndex)> {
         Piece::iter().flat_map(|piece| {
             let bitboard_idx = bitboard_idx(piece);
@@ -301,10 +420,27 @@ impl Bitboards {
         !self.limits | self.all_pieces_by_color(color)
     }
 
+    /// Mask of the last active rank in the moving direction of `color`, on which its pawns promote
+    pub fn promotion_rank_by_color(&self, color: PieceColor) -> Bitboard {
+        if *self.limits == 0 {
+            return Bitboard(u256::ZERO);
+        }
+        let row = match color {
+            PieceColor::White => self.limits.trailing_zeros() / 16,
+            PieceColor::Black => (255 - self.limits.leading_zeros()) / 16,
+        };
+        Bitboard(u256::from(0xFFFFu32) << (row * 16)) & self.limits
+    }
+
     pub fn en_prise_by_color(&self, color: PieceColor) -> Bitboard {
-        let mut en_prise_table = self.en_prise_table.lock().unwrap();
-        if let Some(en_prise) = en_prise_table.get(&(*self.zobrist_hash, color as u8)) {
-            return *en_prise;
+        let key = *self.zobrist_hash ^ color as u64;
+        if let Some(words) = self.en_prise_table.0.load(key) {
+            return Bitboard(
+                words
+                    .iter()
+                    .rev()
+                    .fold(u256::ZERO, |board, word| board << 64 | u256::from(*word)),
+            );
         }
 
         let mut board = Bitboard(u256::ZERO);
@@ -332,70 +468,36 @@ impl Bitboards {
                     }
             }
         }
-        en_prise_table.insert((*self.zobrist_hash, color as u8), board);
+        let words = std::array::from_fn(|i| (*board >> (64 * i)).as_u64());
+        self.en_prise_table.0.store(key, words);
         board
     }
 
-    /// all legal plys by color
-    pub fn all_legal_plys_by_color<T: Default + Extend<Ply>>(&mut self, color: PieceColor) -> T {
-        PieceType::iter().fold(Default::default(), |mut coll, piece_type| {
-            for i in 0..self.piece_list[bitboard_idx(Piece(piece_type, color))].len() {
-                let piece = self.piece_list[bitboard_idx(Piece(piece_type, color))][i];
-                let board = Bitboard::from(piece);
-                let blocked = &self.blocked_mask_for_color(color);
-                let capturable = &self.all_pieces_by_color(color.next());
-                let bitboard_ptr = self.boards.as_ptr();
-                let piece = Piece(piece_type, color);
-                unsafe {
-                    match piece_type {
-                        PieceType::King => {
-                            coll.extend(legality_filter(
-                                board.king_plys(blocked, capturable, bitboard_ptr, piece),
-                                self,
-                            ));
-                        }
-                        PieceType::Queen => {
-                            coll.extend(legality_filter(
-                                board.queen_plys(blocked, capturable, bitboard_ptr, piece),
-                                self,
-                            ));
-                        }
-                        PieceType::Rook => {
-                            coll.extend(legality_filter(
-                                board.rook_plys(blocked, capturable, bitboard_ptr, piece),
-                                self,
-                            ));
-                        }
-
-                        PieceType::Bishop => {
-                            coll.extend(legality_filter(
-                                board.bishop_plys(blocked, capturable, bitboard_ptr, piece),
-                                self,
-                            ));
-                        }
-                        PieceType::Knight => {
-                            coll.extend(legality_filter(
-                                board.knight_plys(blocked, capturable, bitboard_ptr, piece),
-                                self,
-                            ));
-                        }
+    /// Whether the king of `color` stands on a tile threatened by the opponent
+    pub fn in_check(&self, color: PieceColor) -> bool {
+        let king_mask = self.boards[bitboard_idx(Piece(PieceType::King, color))];
+        *king_mask & *self.en_prise_by_color(color.next()) != 0
+    }
 
-                        PieceType::Pawn => coll.extend(legality_filter(
-                            board.pawn_plys(
-                                blocked,
-                                capturable,
-                                bitboard_ptr,
-                                color,
-                                self.unmoved_pieces,
-                                self.en_passant,
-                            ),
-                            self,
-                        )),
-                    };
-                }
+    /// How often the current position has been visited since the last irreversible ply
+    pub fn repetition_count(&self) -> isize {
+        let mut count = 0;
+        for (hash, irreversible) in self.position_history.iter().rev() {
+            if *hash == self.zobrist_hash {
+                count += 1;
             }
-            coll
-        })
+            if *irreversible {
+                break;
+            }
+        }
+        count
+    }
+
+    /// all legal plys by color
+    pub fn all_legal_plys_by_color<T: Default + Extend<Ply>>(&mut self, color: PieceColor) -> T {
+        let mut plys = T::default();
+        self.legal_plys_by_color(color, PlyFilter::All, &mut plys);
+        plys
     }
 
     /// all legal capturing_plys by color
@@ -403,89 +505,163 @@ impl Bitboards {
         &mut self,
         color: PieceColor,
     ) -> T {
-        PieceType::iter().fold(Default::default(), |mut coll, piece_type| {
-            for i in 0..self.piece_list[bitboard_idx(Piece(piece_type, color))].len() {
-                let piece = self.piece_list[bitboard_idx(Piece(piece_type, color))][i];
-                let board = Bitboard::from(piece);
-                let blocked = &self.blocked_mask_for_color(color);
-                let capturable = &self.all_pieces_by_color(color.next());
-                let bitboards_ptr = self.boards.as_ptr();
-                let piece = Piece(piece_type, color);
-                unsafe {
-                    match piece_type {
-                        PieceType::King => {
-                            coll.extend(legality_filter(
-                                captures_only(board.king_plys(
-                                    blocked,
-                                    capturable,
-                                    bitboards_ptr,
-                                    piece,
-                                )),
-                                self,
-                            ));
-                        }
-                        PieceType::Queen => {
-                            coll.extend(legality_filter(
-                                captures_only(board.queen_plys(
-                                    blocked,
-                                    capturable,
-                                    bitboards_ptr,
-                                    piece,
-                                )),
-                                self,
-                            ));
-                        }
-                        PieceType::Rook => {
-                            coll.extend(legality_filter(
-                                captures_only(board.rook_plys(
-                                    blocked,
-                                    capturable,
-                                    bitboards_ptr,
-                                    piece,
-                                )),
-                                self,
-                            ));
-                        }
+        let mut plys = T::default();
+        self.legal_plys_by_color(color, PlyFilter::Captures, &mut plys);
+        plys
+    }
 
-                        PieceType::Bishop => {
-                            coll.extend(legality_filter(
-                                captures_only(board.bishop_plys(
-                                    blocked,
-                                    capturable,
-                                    bitboards_ptr,
-                                    piece,
-                                )),
-                                self,
-                            ));
-                        }
-                        PieceType::Knight => {
-                            coll.extend(legality_filter(
-                                captures_only(board.knight_plys(
-                                    blocked,
-                                    capturable,
-                                    bitboards_ptr,
-                                    piece,
-                                )),
-                                self,
-                            ));
-                        }
+    /// Adds the legal plys of `color` kept by `filter` to `plys`
+    pub fn legal_plys_by_color(
+        &mut self,
+        color: PieceColor,
+        filter: PlyFilter,
+        plys: &mut impl Extend<Ply>,
+    ) {
+        for piece_type in PieceType::iter() {
+            let piece = Piece(piece_type, color);
+            // Checking legality makes and unmakes plys, the board is restored in between
+            for board in self.boards[bitboard_idx(piece)].split() {
+                self.legal_plys_of_piece(piece, board.as_bit_idx(), filter, plys);
+            }
+        }
+    }
 
-                        PieceType::Pawn => coll.extend(legality_filter(
-                            captures_only(board.pawn_plys(
-                                blocked,
-                                capturable,
-                                bitboards_ptr,
-                                color,
-                                self.unmoved_pieces,
-                                self.en_passant,
-                            )),
+    /// Adds the legal plys of `piece` standing on `from` kept by `filter` to `plys`
+    pub fn legal_plys_of_piece(
+        &mut self,
+        piece: Piece,
+        from: BitIndex,
+        filter: PlyFilter,
+        plys: &mut impl Extend<Ply>,
+    ) {
+        let color = piece.1;
+        let board = Bitboard::from(from);
+        let blocked = &self.blocked_mask_for_color(color);
+        let capturable = &self.all_pieces_by_color(color.next());
+        let bitboard_ptr = self.boards.as_ptr();
+        let kept = move |ply: &Ply| filter.keeps(ply);
+        unsafe {
+            match piece.0 {
+                PieceType::King => {
+                    plys.extend(legality_filter(
+                        board
+                            .king_plys(blocked, capturable, bitboard_ptr, piece)
+                            .filter(kept),
+                        self,
+                    ));
+
+                    if filter.keeps_quiet() {
+                        let empty = self.limits & !(*blocked | *capturable);
+                        let unmoved = self.unmoved_pieces;
+                        let rooks = self.boards[bitboard_idx(Piece(PieceType::Rook, color))];
+                        let attacked = self.en_prise_by_color(color.next());
+                        plys.extend(legality_filter(
+                            board.castling_plys(&empty, &unmoved, &rooks, &attacked, piece),
                             self,
-                        )),
-                    };
+                        ));
+                    }
+                }
+                PieceType::Queen => {
+                    plys.extend(legality_filter(
+                        board
+                            .queen_plys(blocked, capturable, bitboard_ptr, piece)
+                            .filter(kept),
+                        self,
+                    ));
+                }
+                PieceType::Rook => {
+                    plys.extend(legality_filter(
+                        board
+                            .rook_plys(blocked, capturable, bitboard_ptr, piece)
+                            .filter(kept),
+                        self,
+                    ));
+                }
+
+                PieceType::Bishop => {
+                    plys.extend(legality_filter(
+                        board
+                            .bishop_plys(blocked, capturable, bitboard_ptr, piece)
+                            .filter(kept),
+                        self,
+                    ));
+                }
+                PieceType::Knight => {
+                    plys.extend(legality_filter(
+                        board
+                            .knight_plys(blocked, capturable, bitboard_ptr, piece)
+                            .filter(kept),
+                        self,
+                    ));
                 }
+
+                PieceType::Pawn => plys.extend(legality_filter(
+                    board
+                        .pawn_plys(
+                            blocked,
+                            capturable,
+                            bitboard_ptr,
+                            color,
+                            self.unmoved_pieces,
+                            self.en_passant,
+                            self.promotion_rank_by_color(color),
+                        )
+                        .filter(kept),
+                    self,
+                )),
+            };
+        }
+    }
+
+    /// Pseudolegal plys of `color`, counted from the move masks. Castling, double pushes and
+    /// en passant are left out, and plys leaving the king in check are included
+    pub fn mobility_by_color(&self, color: PieceColor) -> u32 {
+        let blocked = self.blocked_mask_for_color(color);
+        let capturable = self.all_pieces_by_color(color.next());
+
+        let mut count = 0;
+        for piece in Piece::iter_color(color) {
+            for board in self.boards[bitboard_idx(piece)].split() {
+                count += match piece.0 {
+                    PieceType::King => board.king_move_mask(&blocked, &capturable),
+                    PieceType::Queen => board.queen_move_mask(&blocked, &capturable),
+                    PieceType::Rook => board.rook_move_mask(&blocked, &capturable),
+                    PieceType::Bishop => board.bishop_move_mask(&blocked, &capturable),
+                    PieceType::Knight => board.knight_move_mask(&blocked, &capturable),
+                    PieceType::Pawn => board.pawn_move_mask(&blocked, &capturable, color),
+                }
+                .count_ones();
             }
-            coll
-        })
+        }
+        count
+    }
+}
+
+/// Plys kept by move generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFilter {
+    All,
+    Captures,
+    /// Captures and promotions
+    Noisy,
+    /// Plys neither capturing nor promoting
+    Quiet,
+}
+
+impl PlyFilter {
+    pub fn keeps(&self, ply: &Ply) -> bool {
+        let noisy = ply.capturing.is_some() || ply.promoting.is_some();
+        match self {
+            PlyFilter::All => true,
+            PlyFilter::Captures => ply.capturing.is_some(),
+            PlyFilter::Noisy => noisy,
+            PlyFilter::Quiet => !noisy,
+        }
+    }
+
+    /// Whether quiet plys, like castling, are kept
+    fn keeps_quiet(&self) -> bool {
+        matches!(self, PlyFilter::All | PlyFilter::Quiet)
     }
 }
 
@@ -529,6 +705,18 @@ mod tests {
         assert_eq!(*bitboard, 0b10);
     }
 
+    #[test]
+    fn bitboard_setter_upper_half() {
+        let mut bitboard = Bitboard(u256::ZERO);
+        bitboard.set(255.into(), true);
+        bitboard.set(130.into(), true);
+        bitboard.set(255.into(), false);
+
+        assert_eq!(*bitboard, u256::ONE << 130);
+        assert!(bitboard.get(BitIndex::from(130)));
+        assert!(!bitboard.get(BitIndex::from(255)));
+    }
+
     #[test]
     fn limits_default_amount() {
         let game = Game::default();
@@ -645,6 +833,29 @@ mod tests {
         assert_eq!(en_prise, expected);
     }
 
+    #[test]
+    fn promotion_rank_by_color() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            000
+            000
+            000
+            000
+            "#,
+        );
+
+        let expected_white = Bitboard(u256::from(0b111u32));
+        let expected_black = Bitboard(u256::from(0b111u32) << 48);
+        assert_eq!(
+            boards.promotion_rank_by_color(PieceColor::White),
+            expected_white
+        );
+        assert_eq!(
+            boards.promotion_rank_by_color(PieceColor::Black),
+            expected_black
+        );
+    }
+
     #[test]
     fn all_moves_by_sites_default() {
         let game = Game::default();
@@ -670,6 +881,23 @@ mod tests {
         assert_eq!(white_moves.len(), 8);
     }
 
+    #[test]
+    fn all_moves_include_castling() {
+        let mut boards = Bitboards::new_from_str(
+            r#"
+            0000K000
+            00000000
+            r000k00r
+            "#,
+        );
+        let white_moves: Vec<Ply> = boards.all_legal_plys_by_color(PieceColor::White);
+        let castling: Vec<&Ply> = white_moves
+            .iter()
+            .filter(|ply| ply.also_move.is_some())
+            .collect();
+        assert_eq!(castling.len(), 2);
+    }
+
     #[test]
     fn all_captures_by_sites_complex() {
         let mut boards = Bitboards::new_from_str(
@@ -0,0 +1,595 @@
+use std::{error::Error, fmt::Display};
+
+use ethnum::u256;
+
+use crate::chess_engine::pieces::{PIECE_COMBO_COUNT, Piece, PieceColor, PieceType};
+
+use super::{BitIndex, Bitboard, Bitboards, bitboard_idx};
+
+/// The classical starting position
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Marks a tile outside of `limits` within a rank
+const INACTIVE_TILE: char = '*';
+
+/// Column of the `k` file, whose letter collides with the X-FEN kingside castling right
+const K_FILE: u32 = 10;
+
+/// Position data of the text format which isn't held by `Bitboards`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PositionInfo {
+    pub side_to_move: PieceColor,
+    /// Plys since the last capture or pawn move
+    pub halfmove_clock: u32,
+    /// Starts at 1, incremented after every ply by black
+    pub fullmove_number: u32,
+}
+impl Default for PositionInfo {
+    fn default() -> Self {
+        Self {
+            side_to_move: PieceColor::White,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    MissingPlacement,
+    /// More than 16 ranks
+    TooManyRanks(usize),
+    /// Rank (counted from the top, starting at 1) holds more than 16 tiles
+    RankTooWide(usize),
+    InvalidEmptyCount(String),
+    InvalidPiece(char),
+    InvalidSideToMove(String),
+    /// Castling right without a matching unmoved king and rook
+    InvalidCastling(char),
+    InvalidSquare(String),
+    InvalidCounter(String),
+    UnexpectedField(String),
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingPlacement => write!(f, "missing piece placement"),
+            Self::TooManyRanks(count) => {
+                write!(f, "{} ranks given, 16 is the limit", count)
+            }
+            Self::RankTooWide(rank) => {
+                write!(f, "rank {} is too wide, 16 tiles is the limit", rank)
+            }
+            Self::InvalidEmptyCount(count) => write!(f, "invalid empty tile count '{}'", count),
+            Self::InvalidPiece(char) => write!(f, "invalid piece '{}'", char),
+            Self::InvalidSideToMove(side) => write!(f, "invalid side to move '{}'", side),
+            Self::InvalidCastling(char) => {
+                write!(f, "no unmoved king and rook for castling right '{}'", char)
+            }
+            Self::InvalidSquare(square) => write!(f, "invalid square '{}'", square),
+            Self::InvalidCounter(counter) => write!(f, "invalid move counter '{}'", counter),
+            Self::UnexpectedField(field) => write!(f, "unexpected field '{}'", field),
+        }
+    }
+}
+
+impl Error for FenError {}
+
+impl Bitboards {
+    /// Parses a FEN-like position. Compared to standard FEN, ranks may hold up to 16 tiles,
+    /// up to 16 ranks may be given, and `*` marks an inactive tile.
+    ///
+    /// Castling rights are given in X-FEN style: `K`/`Q` for the outermost rook on either side
+    /// of the king, or the file letter of the rook otherwise (uppercase for white).
+    /// On boards wide enough to have a `k` file, `K` names the rook on that file if there is one,
+    /// Shredder-FEN style, and the outermost rook east of the king otherwise.
+    /// Pawns on the rank in front of their own back rank are considered unmoved.
+    /// Fields following the placement are optional.
+    pub fn from_fen(input: &str) -> Result<(Self, PositionInfo), FenError> {
+        let mut fields = input.split_whitespace();
+        let placement = fields.next().ok_or(FenError::MissingPlacement)?;
+
+        let mut boards = [Bitboard(u256::ZERO); PIECE_COMBO_COUNT];
+        let mut piece_list = vec![vec![]; PIECE_COMBO_COUNT];
+        let mut limits = Bitboard(u256::ZERO);
+
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() > 16 {
+            return Err(FenError::TooManyRanks(ranks.len()));
+        }
+
+        for (row, rank) in ranks.iter().enumerate() {
+            let mut column = 0;
+            let next_tile = |column: &mut u32| {
+                if *column >= 16 {
+                    return Err(FenError::RankTooWide(row + 1));
+                }
+                let idx = BitIndex::from(row as u32 * 16 + *column);
+                *column += 1;
+                Ok(idx)
+            };
+
+            let mut chars = rank.chars().peekable();
+            while let Some(char) = chars.next() {
+                if let Some(digit) = char.to_digit(10) {
+                    let mut count = digit;
+                    let mut count_str = char.to_string();
+                    while let Some(digit) = chars.peek().and_then(|char| char.to_digit(10)) {
+                        count = count.saturating_mul(10).saturating_add(digit);
+                        count_str.push(chars.next().unwrap());
+                    }
+                    if count == 0 || count_str.starts_with('0') {
+                        return Err(FenError::InvalidEmptyCount(count_str));
+                    }
+                    for _ in 0..count {
+                        limits.set(next_tile(&mut column)?, true);
+                    }
+                } else if char == INACTIVE_TILE {
+                    next_tile(&mut column)?;
+                } else {
+                    let piece = Piece::from_fen_char(char).ok_or(FenError::InvalidPiece(char))?;
+                    let idx = next_tile(&mut column)?;
+                    limits.set(idx, true);
+                    boards[bitboard_idx(piece)].set(idx, true);
+                    piece_list[bitboard_idx(piece)].push(idx);
+                }
+            }
+        }
+
+        let side_to_move = match fields.next() {
+            None | Some("w") => PieceColor::White,
+            Some("b") => PieceColor::Black,
+            Some(side) => return Err(FenError::InvalidSideToMove(side.to_string())),
+        };
+
+        // Pawns on their initial rank
+        let mut unmoved_pieces = Bitboard(u256::ZERO);
+        if *limits != 0 {
+            let top_row = limits.trailing_zeros() / 16;
+            let bottom_row = (255 - limits.leading_zeros()) / 16;
+            for (color, row) in [
+                (PieceColor::White, bottom_row.checked_sub(1)),
+                (PieceColor::Black, Some(top_row + 1)),
+            ] {
+                let pawns = &piece_list[bitboard_idx(Piece(PieceType::Pawn, color))];
+                for pawn in pawns.iter().filter(|idx| Some(***idx / 16) == row) {
+                    unmoved_pieces.set(*pawn, true);
+                }
+            }
+        }
+
+        // Castling rights
+        let castling = fields.next().unwrap_or("-");
+        if castling != "-" {
+            for char in castling.chars() {
+                let color = if char.is_ascii_uppercase() {
+                    PieceColor::White
+                } else {
+                    PieceColor::Black
+                };
+                let king = piece_list[bitboard_idx(Piece(PieceType::King, color))]
+                    .first()
+                    .copied()
+                    .ok_or(FenError::InvalidCastling(char))?;
+
+                let mut rooks_on_rank = piece_list[bitboard_idx(Piece(PieceType::Rook, color))]
+                    .iter()
+                    .copied()
+                    .filter(|rook| **rook / 16 == *king / 16);
+                let rook = match char.to_ascii_lowercase() {
+                    'k' => rooks_on_rank
+                        .clone()
+                        .find(|rook| **rook % 16 == K_FILE)
+                        .or_else(|| {
+                            rooks_on_rank
+                                .filter(|rook| **rook > *king)
+                                .max_by_key(|rook| **rook)
+                        }),
+                    'q' => rooks_on_rank
+                        .filter(|rook| **rook < *king)
+                        .min_by_key(|rook| **rook),
+                    file @ 'a'..='p' => {
+                        rooks_on_rank.find(|rook| **rook % 16 == (file as u8 - b'a') as u32)
+                    }
+                    _ => None,
+                }
+                .ok_or(FenError::InvalidCastling(char))?;
+
+                unmoved_pieces.set(king, true);
+                unmoved_pieces.set(rook, true);
+            }
+        }
+
+        let mut new_bitboards = Self::from_placement(
+            boards,
+            piece_list,
+            limits,
+            unmoved_pieces,
+            Bitboard(u256::ZERO),
+            side_to_move,
+        );
+
+        let en_passant = fields.next().unwrap_or("-");
+        if en_passant != "-" {
+            let idx = new_bitboards
+                .parse_square(en_passant)
+                .ok_or(FenError::InvalidSquare(en_passant.to_string()))?;
+            new_bitboards.en_passant = Bitboard::from(idx);
+            new_bitboards.reset_hash();
+        }
+
+        let mut parse_counter = |default: u32| {
+            fields.next().map_or(Ok(default), |counter| {
+                counter
+                    .parse::<u32>()
+                    .map_err(|_| FenError::InvalidCounter(counter.to_string()))
+            })
+        };
+        let halfmove_clock = parse_counter(0)?;
+        let fullmove_number = parse_counter(1)?;
+
+        if let Some(field) = fields.next() {
+            return Err(FenError::UnexpectedField(field.to_string()));
+        }
+
+        Ok((
+            new_bitboards,
+            PositionInfo {
+                side_to_move,
+                halfmove_clock,
+                fullmove_number,
+            },
+        ))
+    }
+
+    /// Serializes the position into the format read by `from_fen`
+    pub fn to_fen(&self, info: &PositionInfo) -> String {
+        let mut fen = String::new();
+
+        // Piece placement
+        let bottom_row = self.bottom_row();
+        for row in 0..=bottom_row {
+            let rank_tiles = (*self.limits >> (row * 16)).as_u16();
+            let width = 16 - rank_tiles.leading_zeros();
+
+            let mut empty = 0;
+            for column in 0..width {
+                let idx = BitIndex::from(row * 16 + column);
+                let tile = if !self.limits.get(idx) {
+                    Some(INACTIVE_TILE)
+                } else {
+                    self.piece_at(idx).map(|piece| piece.as_fen_char())
+                };
+
+                if let Some(char) = tile {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen.push(char);
+                } else {
+                    empty += 1;
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if row != bottom_row {
+                fen.push('/');
+            }
+        }
+
+        // Side to move
+        fen.push_str(match info.side_to_move {
+            PieceColor::White => " w ",
+            PieceColor::Black => " b ",
+        });
+
+        // Castling rights
+        let mut castling = String::new();
+        for color in [PieceColor::White, PieceColor::Black] {
+            let mut rights = vec![];
+            for king in self.piece_list[bitboard_idx(Piece(PieceType::King, color))].iter() {
+                if !self.unmoved_pieces.get(*king) {
+                    continue;
+                }
+                let rooks_on_rank = self.piece_list[bitboard_idx(Piece(PieceType::Rook, color))]
+                    .iter()
+                    .filter(|rook| ***rook / 16 == **king / 16);
+                // `k` is read as the rook on the `k` file whenever there is one
+                let k_file_rook = rooks_on_rank
+                    .clone()
+                    .find(|rook| ***rook % 16 == K_FILE)
+                    .map(|rook| **rook);
+                let mut rooks: Vec<u32> = rooks_on_rank
+                    .filter(|rook| self.unmoved_pieces.get(**rook))
+                    .map(|rook| **rook)
+                    .collect();
+                rooks.sort_unstable();
+
+                let east = rooks.iter().filter(|rook| **rook > **king);
+                for (i, rook) in east.rev().enumerate() {
+                    let outermost = i == 0 && k_file_rook.is_none_or(|k_rook| k_rook == *rook);
+                    rights.push(if outermost { 'k' } else { file_char(*rook) });
+                }
+                let west = rooks.iter().filter(|rook| **rook < **king);
+                for (i, rook) in west.enumerate() {
+                    rights.push(if i == 0 { 'q' } else { file_char(*rook) });
+                }
+            }
+
+            for right in rights {
+                castling.push(match color {
+                    PieceColor::White => right.to_ascii_uppercase(),
+                    PieceColor::Black => right,
+                });
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        fen.push_str(&castling);
+
+        // En passant
+        fen.push(' ');
+        if *self.en_passant != 0 {
+            fen.push_str(&self.square_name(self.en_passant.as_bit_idx()));
+        } else {
+            fen.push('-');
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            info.halfmove_clock, info.fullmove_number
+        ));
+        fen
+    }
+
+    /// Name of a tile, with ranks counted upwards from the last active row
+    pub fn square_name(&self, idx: BitIndex) -> String {
+        let rank = self.bottom_row() - *idx / 16 + 1;
+        format!("{}{}", file_char(*idx), rank)
+    }
+
+    /// Parses a tile name as given by `square_name`, if it is an active tile
+    pub fn parse_square(&self, square: &str) -> Option<BitIndex> {
+        let mut chars = square.chars();
+        let file = chars.next()?;
+        if !('a'..='p').contains(&file) {
+            return None;
+        }
+        let rank = chars.as_str().parse::<u32>().ok()?;
+        let row = (self.bottom_row() + 1).checked_sub(rank)?;
+        if rank == 0 || row >= 16 {
+            return None;
+        }
+
+        let idx = BitIndex::from(row * 16 + (file as u8 - b'a') as u32);
+        self.limits.get(idx).then_some(idx)
+    }
+
+    /// Index of the last row containing active tiles
+    fn bottom_row(&self) -> u32 {
+        (255 - self.limits.leading_zeros().min(255)) / 16
+    }
+}
+
+fn file_char(idx: u32) -> char {
+    (b'a' + (idx % 16) as u8) as char
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chess_engine::{bitboard::Ply, game::Game, pieces::*};
+
+    use super::*;
+
+    #[test]
+    fn starting_position() {
+        let (boards, info) = Bitboards::from_fen(STARTING_FEN).unwrap();
+
+        assert_eq!(boards, Game::default().boards);
+        assert_eq!(boards.limits, Game::default().boards.limits);
+        assert_eq!(info, PositionInfo::default());
+    }
+
+    #[test]
+    fn starting_position_round_trip() {
+        let (boards, info) = Bitboards::from_fen(STARTING_FEN).unwrap();
+
+        assert_eq!(boards.to_fen(&info), STARTING_FEN);
+    }
+
+    #[test]
+    fn default_game_to_fen() {
+        let game = Game::default();
+
+        assert_eq!(game.boards.to_fen(&PositionInfo::default()), STARTING_FEN);
+    }
+
+    #[test]
+    fn placement_only() {
+        let (boards, info) = Bitboards::from_fen("4k3/8/8/8/8/8/8/4K3").unwrap();
+
+        assert_eq!(info, PositionInfo::default());
+        assert_eq!(boards.to_fen(&info), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn side_to_move_changes_hash() {
+        let (white, _) = Bitboards::from_fen("4k3/8/4K3 w - -").unwrap();
+        let (black, info) = Bitboards::from_fen("4k3/8/4K3 b - -").unwrap();
+
+        assert_ne!(white, black);
+        assert_eq!(info.side_to_move, PieceColor::Black);
+    }
+
+    #[test]
+    fn wide_board_with_inactive_tiles() {
+        // trailing inactive tiles of a rank are implied
+        let fen = "r3k6r/**8/12/R3K6R w KQkq - 4 12";
+        let (boards, info) = Bitboards::from_fen(fen).unwrap();
+
+        assert_eq!(boards.limits.count_ones(), 12 * 4 - 4);
+        assert!(!boards.limits.get(&16));
+        assert!(boards.limits.get(&18));
+        assert_eq!(info.halfmove_clock, 4);
+        assert_eq!(info.fullmove_number, 12);
+        assert_eq!(boards.to_fen(&info), fen);
+    }
+
+    #[test]
+    fn sixteen_wide_board() {
+        let fen = "pppppppppppppppp/16/16/PPPPPPPPPPPPPPPP w - - 0 1";
+        let (boards, info) = Bitboards::from_fen(fen).unwrap();
+
+        assert_eq!(boards.limits.count_ones(), 64);
+        assert_eq!(boards.boards[bitboard_idx(BLACK_PAWN)].count_ones(), 16);
+        assert_eq!(boards.to_fen(&info), fen);
+    }
+
+    #[test]
+    fn unmoved_pawns_on_initial_rank() {
+        let (boards, _) = Bitboards::from_fen("8/p7/1p6/8/8/6P1/7P/8").unwrap();
+
+        assert!(boards.unmoved_pieces.get(&16));
+        assert!(!boards.unmoved_pieces.get(&33));
+        assert!(!boards.unmoved_pieces.get(&86));
+        assert!(boards.unmoved_pieces.get(&103));
+    }
+
+    #[test]
+    fn castling_rights_by_file() {
+        let fen = "8/8/R1R2K1R w CKQ - 0 1";
+        let (boards, info) = Bitboards::from_fen(fen).unwrap();
+
+        assert!(boards.unmoved_pieces.get(&32));
+        assert!(boards.unmoved_pieces.get(&34));
+        assert!(boards.unmoved_pieces.get(&37));
+        assert!(boards.unmoved_pieces.get(&39));
+        assert_eq!(boards.to_fen(&info), "8/8/R1R2K1R w KQC - 0 1");
+    }
+
+    #[test]
+    fn castling_rights_on_k_file_round_trip() {
+        // inner rooks on the `k` file, east of the white and west of the black king
+        let fen = "r9rr2k/16/16/R2K6R4R w KPQ - 0 1";
+        let (boards, info) = Bitboards::from_fen(fen).unwrap();
+
+        assert!(boards.unmoved_pieces.get(&48));
+        assert!(boards.unmoved_pieces.get(&58));
+        assert!(boards.unmoved_pieces.get(&63));
+        assert_eq!(boards.to_fen(&info), "r9rr2k/16/16/R2K6R4R w PKQ - 0 1");
+
+        let fen = "r9rr2k/16/16/R2K6R4R w PKQqkl - 0 1";
+        let (boards, info) = Bitboards::from_fen(fen).unwrap();
+
+        assert!(boards.unmoved_pieces.get(&10));
+        assert!(boards.unmoved_pieces.get(&11));
+        assert_eq!(boards.to_fen(&info), fen);
+    }
+
+    #[test]
+    fn partial_castling_rights() {
+        let (boards, _) = Bitboards::from_fen("r3k2r/8/R3K2R w Kq - 0 1").unwrap();
+
+        assert!(boards.unmoved_pieces.get(&39));
+        assert!(!boards.unmoved_pieces.get(&32));
+        assert!(boards.unmoved_pieces.get(&0));
+        assert!(!boards.unmoved_pieces.get(&7));
+    }
+
+    #[test]
+    fn en_passant_square() {
+        let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 3";
+        let (boards, info) = Bitboards::from_fen(fen).unwrap();
+
+        assert_eq!(boards.en_passant, Bitboard::from(BitIndex::from(35)));
+        assert_eq!(boards.to_fen(&info), fen);
+    }
+
+    #[test]
+    fn en_passant_survives_unmake() {
+        let (mut boards, _) = Bitboards::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 3").unwrap();
+        let ply = Ply {
+            moving_piece: WHITE_KING,
+            from: 116.into(),
+            to: 115.into(),
+            ..Default::default()
+        };
+
+        boards.make_ply(&ply);
+        boards.unmake_ply(&ply);
+        assert_eq!(boards.en_passant, Bitboard::from(BitIndex::from(35)));
+    }
+
+    #[test]
+    fn square_names_follow_active_rows() {
+        let (boards, _) = Bitboards::from_fen("3/3/3").unwrap();
+
+        assert_eq!(boards.square_name(0.into()), "a3");
+        assert_eq!(boards.square_name(34.into()), "c1");
+        assert_eq!(boards.parse_square("c1"), Some(34.into()));
+        assert_eq!(boards.parse_square("d1"), None);
+        assert_eq!(boards.parse_square("a4"), None);
+        assert_eq!(boards.parse_square("a0"), None);
+    }
+
+    #[test]
+    fn error_too_wide() {
+        assert_eq!(
+            Bitboards::from_fen("8/17").unwrap_err(),
+            FenError::RankTooWide(2)
+        );
+        assert_eq!(
+            Bitboards::from_fen("pppppppppppppppp*").unwrap_err(),
+            FenError::RankTooWide(1)
+        );
+    }
+
+    #[test]
+    fn error_too_many_ranks() {
+        let fen = vec!["8"; 17].join("/");
+        assert_eq!(
+            Bitboards::from_fen(&fen).unwrap_err(),
+            FenError::TooManyRanks(17)
+        );
+    }
+
+    #[test]
+    fn error_invalid_fields() {
+        assert_eq!(
+            Bitboards::from_fen("").unwrap_err(),
+            FenError::MissingPlacement
+        );
+        assert_eq!(
+            Bitboards::from_fen("8/3x4").unwrap_err(),
+            FenError::InvalidPiece('x')
+        );
+        assert_eq!(
+            Bitboards::from_fen("8/08").unwrap_err(),
+            FenError::InvalidEmptyCount("08".to_string())
+        );
+        assert_eq!(
+            Bitboards::from_fen("8/8 x").unwrap_err(),
+            FenError::InvalidSideToMove("x".to_string())
+        );
+        assert_eq!(
+            Bitboards::from_fen("4k3/8 w K").unwrap_err(),
+            FenError::InvalidCastling('K')
+        );
+        assert_eq!(
+            Bitboards::from_fen("8/8 w - z9").unwrap_err(),
+            FenError::InvalidSquare("z9".to_string())
+        );
+        assert_eq!(
+            Bitboards::from_fen("8/8 w - - one").unwrap_err(),
+            FenError::InvalidCounter("one".to_string())
+        );
+        assert_eq!(
+            Bitboards::from_fen("8/8 w - - 0 1 extra").unwrap_err(),
+            FenError::UnexpectedField("extra".to_string())
+        );
+    }
+}
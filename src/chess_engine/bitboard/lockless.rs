@@ -0,0 +1,175 @@
+//! Fixed capacity hash table which threads can share without locking.
+//! Every slot stores its key xored with all data words. A load whose words were torn by a
+//! concurrent store no longer reproduces the key and is treated as a miss.
+
+use std::sync::{
+    OnceLock,
+    atomic::{AtomicU64, Ordering},
+};
+
+struct Slot<const WORDS: usize> {
+    check: AtomicU64,
+    data: [AtomicU64; WORDS],
+}
+
+impl<const WORDS: usize> Default for Slot<WORDS> {
+    fn default() -> Self {
+        Self {
+            check: AtomicU64::new(0),
+            data: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl<const WORDS: usize> Slot<WORDS> {
+    /// Key and data of the slot, `None` if it was never written.
+    /// A zero key stored with zeroed data is indistinguishable from an empty slot
+    fn load(&self) -> Option<(u64, [u64; WORDS])> {
+        let check = self.check.load(Ordering::Relaxed);
+        let data: [u64; WORDS] = std::array::from_fn(|i| self.data[i].load(Ordering::Relaxed));
+        if check == 0 && data.iter().all(|word| *word == 0) {
+            return None;
+        }
+        Some((data.iter().fold(check, |key, word| key ^ word), data))
+    }
+
+    fn store(&self, key: u64, data: [u64; WORDS]) {
+        for (slot, word) in self.data.iter().zip(data) {
+            slot.store(word, Ordering::Relaxed);
+        }
+        let check = data.iter().fold(key, |check, word| check ^ word);
+        self.check.store(check, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.store(0, [0; WORDS]);
+    }
+}
+
+pub struct LocklessTable<const WORDS: usize> {
+    /// Allocated on first store, so unused tables stay cheap
+    slots: OnceLock<Box<[Slot<WORDS>]>>,
+    capacity: usize,
+}
+
+impl<const WORDS: usize> std::fmt::Debug for LocklessTable<WORDS> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocklessTable")
+            .field("capacity", &self.capacity)
+            .field("allocated", &self.slots.get().is_some())
+            .finish()
+    }
+}
+
+impl<const WORDS: usize> LocklessTable<WORDS> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: OnceLock::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.capacity as u64) as usize
+    }
+
+    fn slots(&self) -> &[Slot<WORDS>] {
+        self.slots.get_or_init(|| {
+            (0..self.capacity)
+                .map(|_| Slot::default())
+                .collect::<Vec<_>>()
+                .into_boxed_slice()
+        })
+    }
+
+    /// Data stored for `key`
+    pub fn load(&self, key: u64) -> Option<[u64; WORDS]> {
+        self.load_slot(key)
+            .filter(|(stored, _)| *stored == key)
+            .map(|(_, data)| data)
+    }
+
+    /// Key and data of whatever occupies the slot of `key`
+    pub fn load_slot(&self, key: u64) -> Option<(u64, [u64; WORDS])> {
+        self.slots.get()?[self.index(key)].load()
+    }
+
+    /// Stores `data` for `key`, replacing the previous occupant of its slot
+    pub fn store(&self, key: u64, data: [u64; WORDS]) {
+        self.slots()[self.index(key)].store(key, data);
+    }
+
+    /// Number of occupied slots
+    pub fn len(&self) -> usize {
+        self.slots.get().map_or(0, |slots| {
+            slots.iter().filter(|slot| slot.load().is_some()).count()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        if let Some(slots) = self.slots.get() {
+            slots.iter().for_each(Slot::clear);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn store_and_load() {
+        let table = LocklessTable::<2>::with_capacity(8);
+        assert!(table.is_empty());
+        assert_eq!(table.load(3), None);
+
+        table.store(3, [7, 9]);
+        assert_eq!(table.load(3), Some([7, 9]));
+        // same slot, different key
+        assert_eq!(table.load(11), None);
+        assert_eq!(table.load_slot(11), Some((3, [7, 9])));
+        assert_eq!(table.len(), 1);
+
+        table.clear();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn torn_store_is_a_miss() {
+        let table = LocklessTable::<2>::with_capacity(8);
+        table.store(3, [7, 9]);
+        // half of a concurrent store of other data
+        table.slots()[3].data[1].store(10, Ordering::Relaxed);
+        assert_eq!(table.load(3), None);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn shared_between_threads() {
+        let table = Arc::new(LocklessTable::<1>::with_capacity(1024));
+        let handles: Vec<_> = (0..4u64)
+            .map(|thread| {
+                let table = table.clone();
+                std::thread::spawn(move || {
+                    for key in (thread * 256)..((thread + 1) * 256) {
+                        table.store(key, [key + 1]);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!((0..1024).all(|key| table.load(key) == Some([key + 1])));
+    }
+}
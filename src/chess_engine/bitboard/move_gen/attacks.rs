@@ -0,0 +1,332 @@
+//! Per tile attack masks of the stepping pieces and rays of the sliding pieces, computed at
+//! compile time. Steps leaving the 16 columns of the board are dropped, where shifting the whole
+//! board would wrap them into the neighbouring row.
+
+use ethnum::u256;
+
+use crate::chess_engine::{bitboard::Bitboard, pieces::PieceColor};
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+];
+const KING_STEPS: [(i32, i32); 8] = [
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+];
+/// White pawns move towards row 0
+const WHITE_PAWN_STEPS: [(i32, i32); 2] = [(-1, -1), (-1, 1)];
+const BLACK_PAWN_STEPS: [(i32, i32); 2] = [(1, -1), (1, 1)];
+
+pub static KNIGHT_ATTACKS: [Bitboard; 256] = stepping_attacks(&KNIGHT_STEPS);
+pub static KING_ATTACKS: [Bitboard; 256] = stepping_attacks(&KING_STEPS);
+static WHITE_PAWN_ATTACKS: [Bitboard; 256] = stepping_attacks(&WHITE_PAWN_STEPS);
+static BLACK_PAWN_ATTACKS: [Bitboard; 256] = stepping_attacks(&BLACK_PAWN_STEPS);
+
+pub static ROOK_RAYS: [Ray; 4] = [ray(0, -1), ray(-1, 0), ray(0, 1), ray(1, 0)];
+pub static BISHOP_RAYS: [Ray; 4] = [ray(-1, -1), ray(-1, 1), ray(1, 1), ray(1, -1)];
+
+/// Tiles in one direction from every tile, up to the edge of the board
+pub struct Ray {
+    tiles: [Bitboard; 256],
+    /// Whether the ray runs towards higher tile indices, so its first tile is the lowest set bit
+    ascending: bool,
+}
+
+impl Ray {
+    /// Tiles along the ray from `tile` until the first `blocked` (exclusive) or `capturable` (inclusive) tile
+    #[inline]
+    fn attacks(&self, tile: u32, blocked: u256, capturable: u256) -> u256 {
+        let ray = *self.tiles[tile as usize];
+        let obstacles = ray & (blocked | capturable);
+        if obstacles == 0 {
+            return ray;
+        }
+        let first = if self.ascending {
+            obstacles.trailing_zeros()
+        } else {
+            255 - obstacles.leading_zeros()
+        };
+        // the ray beyond the first obstacle starts at it, excluding it
+        let mut attacks = ray ^ *self.tiles[first as usize];
+        if blocked & (u256::ONE << first) != 0 {
+            attacks ^= u256::ONE << first;
+        }
+        attacks
+    }
+}
+
+const fn ray(row_step: i32, col_step: i32) -> Ray {
+    let mut tiles = [Bitboard(u256::ZERO); 256];
+    let mut tile = 0;
+    while tile < 256 {
+        let (mut row, mut col) = ((tile / 16) as i32, (tile % 16) as i32);
+        let (mut hi, mut lo) = (0u128, 0u128);
+        loop {
+            row += row_step;
+            col += col_step;
+            if row < 0 || row >= 16 || col < 0 || col >= 16 {
+                break;
+            }
+            let target = row * 16 + col;
+            if target < 128 {
+                lo |= 1 << target;
+            } else {
+                hi |= 1 << (target - 128);
+            }
+        }
+        tiles[tile] = Bitboard(u256::from_words(hi, lo));
+        tile += 1;
+    }
+    Ray {
+        tiles,
+        ascending: row_step > 0 || (row_step == 0 && col_step > 0),
+    }
+}
+
+/// Capture targets of pawns of `color`
+pub fn pawn_attacks(color: PieceColor) -> &'static [Bitboard; 256] {
+    match color {
+        PieceColor::White => &WHITE_PAWN_ATTACKS,
+        PieceColor::Black => &BLACK_PAWN_ATTACKS,
+    }
+}
+
+/// Tiles reached from every tile by one of `steps`, given as (row, column) offsets
+const fn stepping_attacks(steps: &[(i32, i32)]) -> [Bitboard; 256] {
+    let mut table = [Bitboard(u256::ZERO); 256];
+    let mut tile = 0;
+    while tile < 256 {
+        let (row, col) = ((tile / 16) as i32, (tile % 16) as i32);
+        let (mut hi, mut lo) = (0u128, 0u128);
+        let mut i = 0;
+        while i < steps.len() {
+            let (row, col) = (row + steps[i].0, col + steps[i].1);
+            if row >= 0 && row < 16 && col >= 0 && col < 16 {
+                let target = row * 16 + col;
+                if target < 128 {
+                    lo |= 1 << target;
+                } else {
+                    hi |= 1 << (target - 128);
+                }
+            }
+            i += 1;
+        }
+        table[tile] = Bitboard(u256::from_words(hi, lo));
+        tile += 1;
+    }
+    table
+}
+
+impl Bitboard {
+    /// Union of the `table` entries of all set tiles
+    #[inline]
+    pub fn attacks_from(&self, table: &[Bitboard; 256]) -> Self {
+        self.split().fold(Bitboard(u256::ZERO), |acc, tile| {
+            acc | table[*tile.as_bit_idx() as usize]
+        })
+    }
+
+    /// Tiles reached from every set tile along `rays`, until the first `blocked` (exclusive)
+    /// or `capturable` (inclusive) tile
+    #[inline]
+    pub fn sliding_attacks(&self, rays: &[Ray], blocked: &Self, capturable: &Self) -> Self {
+        let mut attacks = u256::ZERO;
+        for tile in self.split() {
+            let tile = tile.trailing_zeros();
+            for ray in rays {
+                attacks |= ray.attacks(tile, **blocked, **capturable);
+            }
+        }
+        Bitboard(attacks)
+    }
+
+    /// Every set tile as a bitboard of its own
+    #[inline]
+    pub fn split(&self) -> impl Iterator<Item = Bitboard> + use<> {
+        let mut remaining = **self;
+        std::iter::from_fn(move || {
+            (remaining != 0).then(|| {
+                let tile = remaining & remaining.wrapping_neg();
+                remaining ^= tile;
+                Bitboard(tile)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::chess_engine::bitboard::BitIndex;
+
+    use super::*;
+
+    /// Attacks derived by shifting the board, which wrap around at the board edges
+    fn shifted(tile: Bitboard, shifts: &[fn(&Bitboard) -> Bitboard]) -> Bitboard {
+        shifts
+            .iter()
+            .fold(Bitboard(u256::ZERO), |acc, shift| acc | shift(&tile))
+    }
+
+    /// Drops targets more than `reach` columns away from `tile`, those wrapped around
+    fn without_wrap(tile: u32, targets: Bitboard, reach: u32) -> Bitboard {
+        targets
+            .split()
+            .filter(|target| (*target.as_bit_idx() % 16).abs_diff(tile % 16) <= reach)
+            .fold(Bitboard(u256::ZERO), |acc, target| acc | target)
+    }
+
+    #[test]
+    fn tables_match_unwrapped_shifts() {
+        let king_shifts = [
+            Bitboard::shift_we,
+            Bitboard::shift_nw,
+            Bitboard::shift_no,
+            Bitboard::shift_ne,
+            Bitboard::shift_ea,
+            Bitboard::shift_se,
+            Bitboard::shift_so,
+            Bitboard::shift_sw,
+        ];
+        let knight_shifts = [
+            Bitboard::shift_nww,
+            Bitboard::shift_nnw,
+            Bitboard::shift_nne,
+            Bitboard::shift_nee,
+            Bitboard::shift_see,
+            Bitboard::shift_sse,
+            Bitboard::shift_ssw,
+            Bitboard::shift_sww,
+        ];
+        let white_pawn_shifts = [Bitboard::shift_nw, Bitboard::shift_ne];
+        let black_pawn_shifts = [Bitboard::shift_sw, Bitboard::shift_se];
+
+        for tile in 0..256u32 {
+            let board = Bitboard::from(BitIndex::from(tile));
+            let idx = tile as usize;
+            assert_eq!(
+                KING_ATTACKS[idx],
+                without_wrap(tile, shifted(board, &king_shifts), 1)
+            );
+            assert_eq!(
+                KNIGHT_ATTACKS[idx],
+                without_wrap(tile, shifted(board, &knight_shifts), 2)
+            );
+            assert_eq!(
+                pawn_attacks(PieceColor::White)[idx],
+                without_wrap(tile, shifted(board, &white_pawn_shifts), 1)
+            );
+            assert_eq!(
+                pawn_attacks(PieceColor::Black)[idx],
+                without_wrap(tile, shifted(board, &black_pawn_shifts), 1)
+            );
+        }
+    }
+
+    #[test]
+    fn no_wrap_on_full_width() {
+        // a knight on the west edge of a 16 wide board
+        let tile = 5 * 16;
+        assert_eq!(KNIGHT_ATTACKS[tile].count_ones(), 4);
+        assert!(
+            KNIGHT_ATTACKS[tile]
+                .split()
+                .all(|target| *target.as_bit_idx() % 16 <= 2)
+        );
+        assert_eq!(KING_ATTACKS[15].count_ones(), 3);
+        assert_eq!(pawn_attacks(PieceColor::White)[16 + 15].count_ones(), 1);
+    }
+
+    #[test]
+    fn sliding_attacks_match_shifted_fills() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0x5eed);
+        for _ in 0..2_000 {
+            // narrower than 16 columns, where filling by shifts is guarded against wrapping
+            let (width, height): (u32, u32) = (rng.random_range(1..16), rng.random_range(1..=16));
+            let limits = (0..height).fold(u256::ZERO, |limits, row| {
+                limits | (((u256::ONE << width) - u256::ONE) << (row * 16))
+            });
+            let random_tiles = |rng: &mut ChaCha8Rng, density: u32| {
+                let words: [u64; 4] = std::array::from_fn(|_| {
+                    (0..density).fold(u64::MAX, |word, _| word & rng.random::<u64>())
+                });
+                let [a, b, c, d] = words.map(u256::from);
+                Bitboard((a | b << 64 | c << 128 | d << 192) & limits)
+            };
+            let own = random_tiles(&mut rng, 2);
+            let blocked = !Bitboard(limits) | own;
+            let capturable = random_tiles(&mut rng, 2) & !blocked;
+            let free = Bitboard(limits) & !blocked;
+            if *free == 0 {
+                continue;
+            }
+            let tile = free
+                .split()
+                .nth(rng.random_range(0..free.count_ones() as usize))
+                .unwrap();
+
+            let rook = [
+                Bitboard::fill_we,
+                Bitboard::fill_no,
+                Bitboard::fill_ea,
+                Bitboard::fill_so,
+            ];
+            let bishop = [
+                Bitboard::fill_nw,
+                Bitboard::fill_ne,
+                Bitboard::fill_se,
+                Bitboard::fill_sw,
+            ];
+            assert_eq!(
+                tile.rook_move_mask(&blocked, &capturable),
+                tile.fill_in_dirs(&rook, &blocked, &capturable)
+            );
+            assert_eq!(
+                tile.bishop_move_mask(&blocked, &capturable),
+                tile.fill_in_dirs(&bishop, &blocked, &capturable)
+            );
+            assert_eq!(
+                tile.queen_move_mask(&blocked, &capturable),
+                tile.fill_in_dirs(&rook, &blocked, &capturable)
+                    | tile.fill_in_dirs(&bishop, &blocked, &capturable)
+            );
+        }
+    }
+
+    #[test]
+    fn sliding_attacks_without_wrap() {
+        let empty = Bitboard(u256::ZERO);
+        // a rook on the east edge of a 16 wide board
+        let rook = Bitboard::from(BitIndex::from(16 + 15));
+        let attacks = rook.rook_move_mask(&empty, &empty);
+        assert_eq!(attacks.count_ones(), 30);
+        assert!(!attacks.get(&32));
+        let bishop = Bitboard::from(BitIndex::from(16));
+        assert_eq!(bishop.bishop_move_mask(&empty, &empty).count_ones(), 15);
+    }
+
+    #[test]
+    fn attacks_of_several_tiles() {
+        let board = Bitboard::from(BitIndex::from(0)) | Bitboard::from(BitIndex::from(255));
+        assert_eq!(board.split().count(), 2);
+        assert_eq!(
+            board.attacks_from(&KING_ATTACKS),
+            KING_ATTACKS[0] | KING_ATTACKS[255]
+        );
+    }
+}
@@ -1,19 +1,11 @@
 use crate::chess_engine::{bitboard::Bitboard, pieces::Piece};
 
-use super::ply::Ply;
-
-const BISHOP_STEP_DIRS: [fn(&Bitboard, &Bitboard, &Bitboard) -> Vec<Bitboard>; 4] = [
-    Bitboard::step_nw,
-    Bitboard::step_ne,
-    Bitboard::step_se,
-    Bitboard::step_sw,
-];
+use super::{attacks::BISHOP_RAYS, ply::Ply};
 
 impl Bitboard {
     /// Cumulative pseudolegal mask of bishop moves
     pub fn bishop_move_mask(&self, blocked: &Self, capturable: &Self) -> Self {
-        let dirs = [Self::fill_nw, Self::fill_ne, Self::fill_se, Self::fill_sw];
-        self.fill_in_dirs(&dirs, blocked, capturable)
+        self.sliding_attacks(&BISHOP_RAYS, blocked, capturable)
     }
 
     /// Mask of threatened positions
@@ -31,13 +23,8 @@ impl Bitboard {
         piece: Piece,
     ) -> impl Iterator<Item = Ply> {
         unsafe {
-            self.multi_step_plys_in_dirs(
-                &BISHOP_STEP_DIRS,
-                blocked,
-                capturable,
-                bitboard_ptr,
-                piece,
-            )
+            let targets = self.bishop_move_mask(blocked, capturable);
+            self.plys_to_targets(&targets, capturable, bitboard_ptr, piece)
         }
     }
 }
@@ -1,31 +1,19 @@
-use ethnum::u256;
+use crate::chess_engine::{
+    bitboard::Bitboard,
+    pieces::{Piece, PieceType},
+};
 
-use crate::chess_engine::{bitboard::Bitboard, pieces::Piece};
-
-use super::ply::Ply;
-
-pub(crate) const KING_DIRS: [fn(&Bitboard) -> Bitboard; 8] = [
-    Bitboard::shift_we,
-    Bitboard::shift_nw,
-    Bitboard::shift_no,
-    Bitboard::shift_ne,
-    Bitboard::shift_ea,
-    Bitboard::shift_se,
-    Bitboard::shift_so,
-    Bitboard::shift_sw,
-];
+use super::{attacks::KING_ATTACKS, move_list::MoveList, ply::Ply};
 
 impl Bitboard {
     /// Cumulative pseudolegal mask of king moves (no castling)
     pub fn king_move_mask(&self, blocked: &Self, _capturable: &Self) -> Self {
-        self.king_moves(blocked, _capturable)
-            .reduce(|acc, e| acc | e)
-            .unwrap_or(Bitboard(u256::ZERO))
+        self.attacks_from(&KING_ATTACKS) & !*blocked
     }
 
     /// Pseudolegal moves by king
     pub fn king_moves(&self, blocked: &Self, _capturable: &Self) -> impl Iterator<Item = Bitboard> {
-        self.shift_in_dirs(&KING_DIRS, blocked, _capturable)
+        self.king_move_mask(blocked, _capturable).split()
     }
 
     /// Mask of threatened positions
@@ -43,9 +31,66 @@ impl Bitboard {
         piece: Piece,
     ) -> impl Iterator<Item = Ply> {
         unsafe {
-            self.single_step_plys_in_dirs(&KING_DIRS, blocked, capturable, bitboard_ptr, piece)
+            let targets = self.king_move_mask(blocked, capturable);
+            self.plys_to_targets(&targets, capturable, bitboard_ptr, piece)
         }
     }
+
+    /// Castling plys of an unmoved king with any unmoved rook on the same rank.
+    /// The king moves two tiles towards the rook, which lands on the tile the king crossed.
+    /// Requires all tiles in between to be `empty`, and the king to neither start on,
+    /// cross, nor land on an `attacked` tile.
+    pub fn castling_plys(
+        &self,
+        empty: &Self,
+        unmoved_pieces: &Self,
+        rooks: &Self,
+        attacked: &Self,
+        piece: Piece,
+    ) -> impl Iterator<Item = Ply> {
+        // at most one castling ply per direction
+        let mut plys = MoveList::<2>::default();
+        if **self & **unmoved_pieces == 0 || **self & **attacked != 0 {
+            return plys.into_iter();
+        }
+
+        let rank = *self.as_bit_idx() / 16;
+        let castling_rooks = *rooks & *unmoved_pieces;
+        for dir in [Bitboard::shift_we, Bitboard::shift_ea] {
+            // walk over empty tiles until hitting the first occupied or inactive one
+            let mut steps = 0;
+            let mut current = dir(self);
+            while *current != 0 && *current & **empty != 0 && *current.as_bit_idx() / 16 == rank {
+                steps += 1;
+                current = dir(&current);
+            }
+
+            // rook has to be far enough away for the king to jump two tiles
+            if steps < 2 || *current & *castling_rooks == 0 || *current.as_bit_idx() / 16 != rank {
+                continue;
+            }
+
+            let crossing = dir(self);
+            let landing = dir(&crossing);
+            if *(crossing | landing) & **attacked != 0 {
+                continue;
+            }
+
+            plys.push(Ply {
+                moving_piece: piece,
+                from: self.as_bit_idx(),
+                to: landing.as_bit_idx(),
+                also_move: Some((
+                    Piece(PieceType::Rook, piece.1),
+                    current.as_bit_idx(),
+                    crossing.as_bit_idx(),
+                )),
+                ..Default::default()
+            });
+        }
+
+        plys.into_iter()
+    }
 }
 
 #[cfg(test)]
@@ -54,9 +99,34 @@ mod tests {
 
     use crate::chess_engine::{
         bitboard::{Bitboards, Ply, bitboard_idx},
-        pieces::{PieceColor, WHITE_KING},
+        pieces::{BLACK_KING, BLACK_ROOK, PieceColor, WHITE_KING, WHITE_ROOK},
     };
 
+    fn castling_plys_for(boards: &Bitboards, color: PieceColor) -> Vec<Ply> {
+        let king = if color == PieceColor::White {
+            WHITE_KING
+        } else {
+            BLACK_KING
+        };
+        let rook = if color == PieceColor::White {
+            WHITE_ROOK
+        } else {
+            BLACK_ROOK
+        };
+        let empty = boards.limits
+            & !(boards.all_pieces_by_color(PieceColor::White)
+                | boards.all_pieces_by_color(PieceColor::Black));
+        boards.boards[bitboard_idx(king)]
+            .castling_plys(
+                &empty,
+                &boards.unmoved_pieces,
+                &boards.boards[bitboard_idx(rook)],
+                &boards.en_prise_by_color(color.next()),
+                king,
+            )
+            .collect()
+    }
+
     #[test]
     fn king_move_mask() {
         let boards = Bitboards::new_from_str(
@@ -176,4 +246,113 @@ mod tests {
         assert_eq!(plys.len(), 8);
         assert!(plys.pop().unwrap().capturing.is_some())
     }
+
+    #[test]
+    fn castling_plys_both_sides() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            R000K00R
+            00000000
+            r000k00r
+            "#,
+        );
+
+        let plys = castling_plys_for(&boards, PieceColor::White);
+        assert_eq!(plys.len(), 2);
+        assert!(plys.contains(&Ply {
+            moving_piece: WHITE_KING,
+            from: 36.into(),
+            to: 38.into(),
+            also_move: Some((WHITE_ROOK, 39.into(), 37.into())),
+            ..Default::default()
+        }));
+        assert!(plys.contains(&Ply {
+            moving_piece: WHITE_KING,
+            from: 36.into(),
+            to: 34.into(),
+            also_move: Some((WHITE_ROOK, 32.into(), 35.into())),
+            ..Default::default()
+        }));
+
+        let plys = castling_plys_for(&boards, PieceColor::Black);
+        assert_eq!(plys.len(), 2);
+    }
+
+    #[test]
+    fn castling_plys_wide_board() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            0000000000
+            r00000k00r
+            "#,
+        );
+
+        let plys = castling_plys_for(&boards, PieceColor::White);
+        assert_eq!(plys.len(), 2);
+        assert!(plys.contains(&Ply {
+            moving_piece: WHITE_KING,
+            from: 22.into(),
+            to: 20.into(),
+            also_move: Some((WHITE_ROOK, 16.into(), 21.into())),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn castling_plys_blocked_path() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            00000000
+            rn00k0br
+            "#,
+        );
+
+        let plys = castling_plys_for(&boards, PieceColor::White);
+        assert!(plys.is_empty());
+    }
+
+    #[test]
+    fn castling_plys_attacked_path() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            00000R00
+            00000000
+            r000k00r
+            "#,
+        );
+
+        // Kingside crosses an attacked tile, queenside remains possible
+        let plys = castling_plys_for(&boards, PieceColor::White);
+        assert_eq!(plys.len(), 1);
+        assert_eq!(plys[0].to, 34.into());
+    }
+
+    #[test]
+    fn castling_plys_in_check() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            0000R000
+            00000000
+            r000k00r
+            "#,
+        );
+
+        let plys = castling_plys_for(&boards, PieceColor::White);
+        assert!(plys.is_empty());
+    }
+
+    #[test]
+    fn castling_plys_moved_rook() {
+        let mut boards = Bitboards::new_from_str(
+            r#"
+            00000000
+            r000k00r
+            "#,
+        );
+        boards.unmoved_pieces.set(23.into(), false);
+
+        let plys = castling_plys_for(&boards, PieceColor::White);
+        assert_eq!(plys.len(), 1);
+        assert_eq!(plys[0].also_move.unwrap().1, 16.into());
+    }
 }
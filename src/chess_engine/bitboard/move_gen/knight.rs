@@ -1,26 +1,11 @@
-use ethnum::u256;
-
 use crate::chess_engine::{bitboard::Bitboard, pieces::Piece};
 
-use super::ply::Ply;
-
-const KNIGHT_DIRS: [fn(&Bitboard) -> Bitboard; 8] = [
-    Bitboard::shift_nww,
-    Bitboard::shift_nnw,
-    Bitboard::shift_nne,
-    Bitboard::shift_nee,
-    Bitboard::shift_see,
-    Bitboard::shift_sse,
-    Bitboard::shift_ssw,
-    Bitboard::shift_sww,
-];
+use super::{attacks::KNIGHT_ATTACKS, ply::Ply};
 
 impl Bitboard {
     /// Cumulative pseudolegal mask of knight moves
     pub fn knight_move_mask(&self, blocked: &Self, _capturable: &Self) -> Self {
-        self.knight_moves(blocked, _capturable)
-            .reduce(|acc, e| acc | e)
-            .unwrap_or(Self(u256::ZERO))
+        self.attacks_from(&KNIGHT_ATTACKS) & !*blocked
     }
 
     /// Pseudolegal moves by knight
@@ -29,7 +14,7 @@ impl Bitboard {
         blocked: &Self,
         _capturable: &Self,
     ) -> impl Iterator<Item = Bitboard> {
-        self.shift_in_dirs(&KNIGHT_DIRS, blocked, _capturable)
+        self.knight_move_mask(blocked, _capturable).split()
     }
 
     /// Mask of threatened positions
@@ -47,7 +32,8 @@ impl Bitboard {
         piece: Piece,
     ) -> impl Iterator<Item = Ply> {
         unsafe {
-            self.single_step_plys_in_dirs(&KNIGHT_DIRS, blocked, capturable, bitboard_ptr, piece)
+            let targets = self.knight_move_mask(blocked, capturable);
+            self.plys_to_targets(&targets, capturable, bitboard_ptr, piece)
         }
     }
 }
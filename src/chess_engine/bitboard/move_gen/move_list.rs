@@ -0,0 +1,251 @@
+//! List of plys stored inline up to a fixed capacity, so generating plys usually doesn't allocate
+
+use std::{
+    fmt::Debug,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+};
+
+use super::ply::Ply;
+
+/// Default inline capacity of a move list. Covers any 8x8 position, larger boards with many
+/// pieces may exceed it, moving the plys to the heap
+pub const MAX_PLYS: usize = 256;
+
+#[derive(Clone)]
+pub struct MoveList<const N: usize = MAX_PLYS> {
+    plys: [MaybeUninit<Ply>; N],
+    len: usize,
+    /// Holds all plys in place of `plys` once more than `N` have been pushed
+    spilled: Option<Vec<Ply>>,
+}
+
+impl<const N: usize> Default for MoveList<N> {
+    fn default() -> Self {
+        Self {
+            plys: [MaybeUninit::uninit(); N],
+            len: 0,
+            spilled: None,
+        }
+    }
+}
+
+impl<const N: usize> Debug for MoveList<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<const N: usize> MoveList<N> {
+    #[inline]
+    pub fn push(&mut self, ply: Ply) {
+        if let Some(spilled) = &mut self.spilled {
+            spilled.push(ply);
+        } else if self.len < N {
+            self.plys[self.len] = MaybeUninit::new(ply);
+            self.len += 1;
+        } else {
+            self.spill().push(ply);
+        }
+    }
+
+    /// Moves the inline plys to the heap, from where all further plys are stored
+    #[cold]
+    fn spill(&mut self) -> &mut Vec<Ply> {
+        let mut spilled = Vec::with_capacity(N * 2);
+        spilled.extend_from_slice(self);
+        self.spilled.insert(spilled)
+    }
+
+    /// Drops all plys from `len` on
+    pub fn truncate(&mut self, len: usize) {
+        if let Some(spilled) = &mut self.spilled {
+            spilled.truncate(len);
+        } else {
+            self.len = self.len.min(len);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if let Some(spilled) = &mut self.spilled {
+            spilled.clear();
+        } else {
+            self.len = 0;
+        }
+    }
+
+    /// Keeps the plys `keep` returns true for, in their order. `keep` may modify the plys
+    pub fn retain_mut(&mut self, mut keep: impl FnMut(&mut Ply) -> bool) {
+        if let Some(spilled) = &mut self.spilled {
+            spilled.retain_mut(keep);
+            return;
+        }
+
+        let mut kept = 0;
+        for i in 0..self.len {
+            let mut ply = self[i];
+            if keep(&mut ply) {
+                self.plys[kept] = MaybeUninit::new(ply);
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+
+impl<const N: usize> Deref for MoveList<N> {
+    type Target = [Ply];
+
+    #[inline]
+    fn deref(&self) -> &[Ply] {
+        if let Some(spilled) = &self.spilled {
+            return spilled;
+        }
+        // SAFETY: the first `len` plys have been written by `push`
+        unsafe { std::slice::from_raw_parts(self.plys.as_ptr().cast(), self.len) }
+    }
+}
+
+impl<const N: usize> DerefMut for MoveList<N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [Ply] {
+        if let Some(spilled) = &mut self.spilled {
+            return spilled;
+        }
+        // SAFETY: the first `len` plys have been written by `push`
+        unsafe { std::slice::from_raw_parts_mut(self.plys.as_mut_ptr().cast(), self.len) }
+    }
+}
+
+impl<const N: usize> Extend<Ply> for MoveList<N> {
+    fn extend<T: IntoIterator<Item = Ply>>(&mut self, iter: T) {
+        for ply in iter {
+            self.push(ply);
+        }
+    }
+}
+
+impl<const N: usize> FromIterator<Ply> for MoveList<N> {
+    fn from_iter<T: IntoIterator<Item = Ply>>(iter: T) -> Self {
+        let mut list = Self::default();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a MoveList<N> {
+    type Item = &'a Ply;
+    type IntoIter = std::slice::Iter<'a, Ply>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<const N: usize> IntoIterator for MoveList<N> {
+    type Item = Ply;
+    type IntoIter = IntoIter<N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            list: self,
+            next: 0,
+        }
+    }
+}
+
+/// Owning iterator over the plys of a `MoveList`
+pub struct IntoIter<const N: usize> {
+    list: MoveList<N>,
+    next: usize,
+}
+
+impl<const N: usize> Iterator for IntoIter<N> {
+    type Item = Ply;
+
+    #[inline]
+    fn next(&mut self) -> Option<Ply> {
+        let ply = self.list.get(self.next).copied();
+        self.next += 1;
+        ply
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.list.len().saturating_sub(self.next);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const N: usize> ExactSizeIterator for IntoIter<N> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::chess_engine::pieces::{WHITE_KNIGHT, WHITE_PAWN};
+
+    use super::*;
+
+    fn ply(to: u32) -> Ply {
+        Ply {
+            moving_piece: WHITE_KNIGHT,
+            to: to.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn push_and_iterate() {
+        let mut list: MoveList = MoveList::default();
+        assert!(list.is_empty());
+        list.push(ply(1));
+        list.extend([ply(2), ply(3)]);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[1], ply(2));
+        assert_eq!(
+            list.into_iter().map(|ply| *ply.to).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn retain_keeps_order_and_changes() {
+        let mut list: MoveList = (1..=6).map(ply).collect();
+        list.retain_mut(|ply| {
+            ply.moving_piece = WHITE_PAWN;
+            *ply.to % 2 == 0
+        });
+        assert_eq!(
+            list.iter().map(|ply| *ply.to).collect::<Vec<_>>(),
+            vec![2, 4, 6]
+        );
+        assert!(list.iter().all(|ply| ply.moving_piece == WHITE_PAWN));
+
+        list.truncate(1);
+        assert_eq!(list.len(), 1);
+        list.clear();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn push_beyond_capacity_spills() {
+        let mut list = MoveList::<2>::default();
+        list.extend((0..5).map(ply));
+        assert_eq!(
+            list.iter().map(|ply| *ply.to).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4]
+        );
+
+        list.retain_mut(|ply| *ply.to % 2 == 0);
+        assert_eq!(
+            list.clone()
+                .into_iter()
+                .map(|ply| *ply.to)
+                .collect::<Vec<_>>(),
+            vec![0, 2, 4]
+        );
+
+        list.truncate(1);
+        assert_eq!(list.len(), 1);
+        list.clear();
+        assert!(list.is_empty());
+    }
+}
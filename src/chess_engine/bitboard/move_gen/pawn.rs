@@ -1,11 +1,20 @@
-use ethnum::u256;
-
 use crate::chess_engine::{
     bitboard::{Bitboard, all_pieces_by_color_from_ptr_iter},
     pieces::{Piece, PieceColor, PieceType, PieceWithBitboard},
 };
 
-use super::ply::Ply;
+use super::{attacks::pawn_attacks, move_list::MoveList, ply::Ply};
+
+/// Piece types a pawn can promote into upon reaching the last active rank
+pub const PROMOTION_TARGETS: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+/// Most plys of a single pawn, a push and a double push as well as both captures promoting
+const PAWN_PLYS: usize = 4 * PROMOTION_TARGETS.len();
 
 fn pawn_dir(color: PieceColor) -> fn(&Bitboard) -> Bitboard {
     if color == PieceColor::White {
@@ -15,27 +24,33 @@ fn pawn_dir(color: PieceColor) -> fn(&Bitboard) -> Bitboard {
     }
 }
 
+/// Pushes `ply` into `moves`, or one ply per promotion target if it lands on `promotion_rank`
+fn push_with_promotions(moves: &mut MoveList<PAWN_PLYS>, ply: Ply, promotion_rank: &Bitboard) {
+    if *Bitboard::from(ply.to) & **promotion_rank != 0 {
+        moves.extend(PROMOTION_TARGETS.iter().map(|&piece_type| Ply {
+            promoting: Some(Piece(piece_type, ply.moving_piece.1)),
+            ..ply
+        }));
+    } else {
+        moves.push(ply);
+    }
+}
+
 impl Bitboard {
+    /// Cumulative pseudolegal mask of single pushes and captures of pawns, no double pushes or en passant
+    pub fn pawn_move_mask(&self, blocked: &Self, capturable: &Self, color: PieceColor) -> Self {
+        let pushes = pawn_dir(color)(self) & !(*blocked | *capturable);
+        pushes | (self.attacks_from(pawn_attacks(color)) & *capturable)
+    }
+
     /// Mask of threatened positions
     pub fn pawn_en_prise_mask(&self, blocked: &Self, color: PieceColor) -> Self {
-        let mut mask = Bitboard(u256::ZERO);
-        let normal = pawn_dir(color)(self);
-        // Normal captures
-        let capture_one = normal.shift_we();
-        if *capture_one & **blocked == 0 {
-            mask |= capture_one;
-        }
-
-        let capture_two = normal.shift_ea();
-        if *capture_two & **blocked == 0 {
-            mask |= capture_two;
-        }
-
-        mask
+        self.attacks_from(pawn_attacks(color)) & !*blocked
     }
 
     /// # Safety
     /// This functions requires a valid pointer to the bitboard array for `bitboard_ptr`,
+    #[allow(clippy::too_many_arguments)]
     pub unsafe fn pawn_plys(
         &self,
         blocked: &Self,
@@ -44,41 +59,48 @@ impl Bitboard {
         color: PieceColor,
         unmoved_pieces: Self,
         en_passant: Self,
+        promotion_rank: Self,
     ) -> impl Iterator<Item = Ply> {
         let dir = pawn_dir(color);
-        let mut moves = vec![];
+        let mut moves = MoveList::default();
 
         let bit_idx = self.as_bit_idx();
 
         let normal = dir(self);
         if *normal != 0 && *normal & **blocked == 0 && *normal & **capturable == 0 {
-            moves.push(Ply {
-                moving_piece: Piece(PieceType::Pawn, color),
-                from: bit_idx,
-                to: normal.as_bit_idx(),
-                ..Default::default()
-            });
+            push_with_promotions(
+                &mut moves,
+                Ply {
+                    moving_piece: Piece(PieceType::Pawn, color),
+                    from: bit_idx,
+                    to: normal.as_bit_idx(),
+                    ..Default::default()
+                },
+                &promotion_rank,
+            );
 
             // Normal push was possible, check for double
             if **self & *unmoved_pieces != 0 {
                 let double = dir(&normal);
-                if *double != 0 && *double & **blocked == 0 && *normal & **capturable == 0 {
-                    moves.push(Ply {
-                        moving_piece: Piece(PieceType::Pawn, color),
-                        from: bit_idx,
-                        to: double.as_bit_idx(),
-                        en_passant_board: Some(normal),
-                        ..Default::default()
-                    });
+                if *double != 0 && *double & **blocked == 0 && *double & **capturable == 0 {
+                    push_with_promotions(
+                        &mut moves,
+                        Ply {
+                            moving_piece: Piece(PieceType::Pawn, color),
+                            from: bit_idx,
+                            to: double.as_bit_idx(),
+                            en_passant_board: Some(normal),
+                            ..Default::default()
+                        },
+                        &promotion_rank,
+                    );
                 }
             }
         }
 
         // Normal captures
-        let capture_dirs = [Bitboard::shift_we, Bitboard::shift_ea];
-        for dir in capture_dirs {
+        for capture in self.attacks_from(pawn_attacks(color)).split() {
             let mut capturing = None;
-            let capture = dir(&normal);
             if *capture & **capturable != 0 {
                 // There is a capture present
                 let capturable_iter =
@@ -89,26 +111,30 @@ impl Bitboard {
                         capturing = Some((piece_type, capture.as_bit_idx()))
                     }
                 }
-                moves.push(Ply {
-                    moving_piece: Piece(PieceType::Pawn, color),
-                    from: bit_idx,
-                    to: capture.as_bit_idx(),
-                    capturing,
-                    ..Default::default()
-                })
+                push_with_promotions(
+                    &mut moves,
+                    Ply {
+                        moving_piece: Piece(PieceType::Pawn, color),
+                        from: bit_idx,
+                        to: capture.as_bit_idx(),
+                        capturing,
+                        ..Default::default()
+                    },
+                    &promotion_rank,
+                );
             }
 
-            // en passant
+            // en passant, only against the opposing pawn which just passed the tile
             if *en_passant != 0 {
-                let capture = dir(&normal);
-                if *capture & *en_passant != 0 {
+                let passed_pawn = pawn_dir(color.next())(&capture);
+                if *capture & *en_passant != 0 && *passed_pawn & **capturable != 0 {
                     moves.push(Ply {
                         moving_piece: Piece(PieceType::Pawn, color),
                         from: bit_idx,
                         to: capture.as_bit_idx(),
                         capturing: Some((
                             Piece(PieceType::Pawn, color.next()),
-                            pawn_dir(color.next())(&capture).as_bit_idx(),
+                            passed_pawn.as_bit_idx(),
                         )),
                         ..Default::default()
                     });
@@ -126,7 +152,7 @@ mod tests {
 
     use crate::chess_engine::{
         bitboard::{Bitboards, Ply, bitboard_idx},
-        pieces::{BLACK_PAWN, PieceColor, WHITE_PAWN},
+        pieces::{BLACK_PAWN, BLACK_QUEEN, PieceColor, WHITE_PAWN, WHITE_QUEEN, WHITE_ROOK},
     };
 
     #[test]
@@ -182,6 +208,7 @@ mod tests {
         let boards = Bitboards::new_from_str(
             r#"
             000
+            000
             P00
             0p0
             "#,
@@ -197,6 +224,7 @@ mod tests {
                     PieceColor::White,
                     boards.unmoved_pieces,
                     boards.en_passant,
+                    boards.promotion_rank_by_color(PieceColor::White),
                 )
                 .collect()
         };
@@ -211,6 +239,7 @@ mod tests {
             pP0
             000
             000
+            000
             "#,
         );
         let board = boards.boards[bitboard_idx(BLACK_PAWN)];
@@ -220,6 +249,7 @@ mod tests {
             000
             p00
             000
+            000
             "#,
         );
         let en_passant = en_passant.boards[bitboard_idx(WHITE_PAWN)];
@@ -233,6 +263,7 @@ mod tests {
                     PieceColor::Black,
                     boards.unmoved_pieces,
                     en_passant,
+                    boards.promotion_rank_by_color(PieceColor::Black),
                 )
                 .collect()
         };
@@ -240,6 +271,74 @@ mod tests {
         assert!(plys.pop().unwrap().capturing.is_some())
     }
 
+    #[test]
+    fn pawn_plys_no_en_passant_without_passed_pawn() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            000
+            000
+            000
+            p00
+            "#,
+        );
+        let board = boards.boards[bitboard_idx(WHITE_PAWN)];
+
+        // en passant tile left behind by a pawn of the same color
+        let en_passant = Bitboards::new_from_str(
+            r#"
+            000
+            000
+            0p0
+            000
+            "#,
+        );
+        let en_passant = en_passant.boards[bitboard_idx(WHITE_PAWN)];
+
+        let plys: Vec<Ply> = unsafe {
+            board
+                .pawn_plys(
+                    &boards.blocked_mask_for_color(PieceColor::White),
+                    &boards.all_pieces_by_color(PieceColor::Black),
+                    boards.boards.as_ptr(),
+                    PieceColor::White,
+                    boards.unmoved_pieces,
+                    en_passant,
+                    boards.promotion_rank_by_color(PieceColor::White),
+                )
+                .collect()
+        };
+        assert!(plys.iter().all(|ply| ply.capturing.is_none()));
+    }
+
+    #[test]
+    fn pawn_double_push_blocked_by_opponent() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            000
+            0P0
+            000
+            0p0
+            "#,
+        );
+        let board = boards.boards[bitboard_idx(WHITE_PAWN)];
+
+        let plys: Vec<Ply> = unsafe {
+            board
+                .pawn_plys(
+                    &boards.blocked_mask_for_color(PieceColor::White),
+                    &boards.all_pieces_by_color(PieceColor::Black),
+                    boards.boards.as_ptr(),
+                    PieceColor::White,
+                    boards.unmoved_pieces,
+                    boards.en_passant,
+                    boards.promotion_rank_by_color(PieceColor::White),
+                )
+                .collect()
+        };
+        assert_eq!(plys.len(), 1);
+        assert!(plys[0].en_passant_board.is_none());
+    }
+
     #[test]
     fn pawn_cannot_step_on_king() {
         let boards = Bitboards::new_from_str(
@@ -259,9 +358,75 @@ mod tests {
                     PieceColor::White,
                     boards.unmoved_pieces,
                     boards.en_passant,
+                    boards.promotion_rank_by_color(PieceColor::White),
                 )
                 .collect()
         };
         assert_eq!(plys.len(), 0);
     }
+
+    #[test]
+    fn pawn_plys_promotion() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            000
+            0p0
+            000
+            "#,
+        );
+        let board = boards.boards[bitboard_idx(WHITE_PAWN)];
+
+        let plys: Vec<Ply> = unsafe {
+            board
+                .pawn_plys(
+                    &boards.blocked_mask_for_color(PieceColor::White),
+                    &boards.all_pieces_by_color(PieceColor::Black),
+                    boards.boards.as_ptr(),
+                    PieceColor::White,
+                    boards.unmoved_pieces,
+                    boards.en_passant,
+                    boards.promotion_rank_by_color(PieceColor::White),
+                )
+                .collect()
+        };
+        // Pawn is unmoved, but the double push is blocked by the board edge
+        assert_eq!(plys.len(), 4);
+        assert!(plys.iter().all(|ply| ply.promoting.is_some()));
+        assert!(plys.iter().any(|ply| ply.promoting == Some(WHITE_QUEEN)));
+    }
+
+    #[test]
+    fn pawn_plys_capture_promotion() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            0000
+            00P0
+            0r00
+            "#,
+        );
+        let board = boards.boards[bitboard_idx(BLACK_PAWN)];
+
+        let plys: Vec<Ply> = unsafe {
+            board
+                .pawn_plys(
+                    &boards.blocked_mask_for_color(PieceColor::Black),
+                    &boards.all_pieces_by_color(PieceColor::White),
+                    boards.boards.as_ptr(),
+                    PieceColor::Black,
+                    boards.unmoved_pieces,
+                    boards.en_passant,
+                    boards.promotion_rank_by_color(PieceColor::Black),
+                )
+                .collect()
+        };
+        assert_eq!(plys.len(), 8);
+        assert!(plys.contains(&Ply {
+            moving_piece: BLACK_PAWN,
+            from: 18.into(),
+            to: 33.into(),
+            capturing: Some((WHITE_ROOK, 33.into())),
+            promoting: Some(BLACK_QUEEN),
+            ..Default::default()
+        }));
+    }
 }
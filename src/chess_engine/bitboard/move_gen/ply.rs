@@ -1,8 +1,11 @@
 use ethnum::u256;
 
 use crate::chess_engine::{
-    bitboard::{BitIndex, Bitboard, Bitboards, all_pieces_by_color_from_ptr_iter, bitboard_idx},
+    bitboard::{
+        BitIndex, Bitboard, Bitboards, UnmakeState, all_pieces_by_color_from_ptr_iter, bitboard_idx,
+    },
     pieces::{Piece, PieceColor, PieceType, PieceWithBitboard},
+    zobrist::CHANGE_PLAYER_INDEX,
 };
 use std::{cmp::Ordering, fmt::Display};
 
@@ -16,7 +19,12 @@ pub struct Ply {
     pub capturing: Option<(Piece, BitIndex)>,
     pub also_move: Option<(Piece, BitIndex, BitIndex)>,
     pub en_passant_board: Option<Bitboard>,
+    /// Piece the moving pawn turns into upon reaching the last rank
+    pub promoting: Option<Piece>,
     pub pv_move: bool,
+    /// Set by the search for ordering: static exchange of captures, killer and history score of
+    /// quiet plys. Captures losing material are ordered after the quiet plys
+    pub order_score: i32,
 }
 
 impl Display for Ply {
@@ -28,9 +36,13 @@ impl Display for Ply {
         if let Some((captured, _)) = self.capturing {
             capture.push_str(&format!(" x{}", captured.as_char()));
         }
+        let mut promotion = "".to_string();
+        if let Some(promoted) = self.promoting {
+            promotion.push_str(&format!(" ={}", promoted.as_char()));
+        }
 
         // Non-standard representation, but fully detailed
-        write!(f, "{} {}{}{}", piece, from, to, capture)
+        write!(f, "{} {}{}{}{}", piece, from, to, capture, promotion)
     }
 }
 
@@ -49,19 +61,37 @@ impl Ord for Ply {
             _ => (),
         }
 
-        // using MVV_LVA (Most Valuable Victim, Least Valuable Attacker)
+        // by static exchange, ties broken using MVV_LVA (Most Valuable Victim, Least Valuable Attacker)
         match (self.capturing, other.capturing) {
+            (None, Some(_)) if other.order_score < 0 => Ordering::Greater,
             (None, Some(_)) => Ordering::Less,
+            (Some(_), None) if self.order_score < 0 => Ordering::Less,
             (Some(_), None) => Ordering::Greater,
-            (None, None) => self.moving_piece.0.cmp(&other.moving_piece.0),
-            _ => self
-                .capture_sorting_value()
-                .cmp(&other.capture_sorting_value()),
+            (None, None) => self
+                .order_score
+                .cmp(&other.order_score)
+                .then(self.moving_piece.0.cmp(&other.moving_piece.0)),
+            _ => self.order_score.cmp(&other.order_score).then(
+                self.capture_sorting_value()
+                    .cmp(&other.capture_sorting_value()),
+            ),
         }
     }
 }
 
 impl Ply {
+    /// Placeholder for a passed turn of `color` in a line of plys, see `Bitboards::make_null_ply`
+    pub fn null(color: PieceColor) -> Self {
+        Self {
+            moving_piece: Piece(PieceType::King, color),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.from == self.to
+    }
+
     fn capture_sorting_value(&self) -> u8 {
         if let Some(captured) = self.capturing {
             let victim_value = match captured.0.0 {
@@ -88,90 +118,49 @@ impl Ply {
 }
 
 impl Bitboard {
-    /// Returns a iterator of all unblocked single-step plys
+    /// Returns a iterator of the plys to every tile of `targets`
     ///
     /// # Safety
     /// Will require `bitboard_ptr` to be valid until all movement generation has been done.
     /// The pointer needs to be the Bitboards array of Bitboards
-    pub unsafe fn single_step_plys_in_dirs(
+    pub unsafe fn plys_to_targets(
         &self,
-        dirs: &[fn(&Self) -> Self],
-        blocked: &Self,
+        targets: &Self,
         capturable: &Self,
         bitboard_ptr: *const Bitboard,
         by_piece: Piece,
-    ) -> impl Iterator<Item = Ply> {
-        dirs.iter()
-            .map(|dir| dir(self))
-            .filter(|board| **board != 0 && **board & **blocked == 0)
-            .map(move |board| {
-                let mut capturing = None;
-                if *board & **capturable != 0 {
-                    // There is a capture present
-                    let capturing_iter = unsafe {
-                        all_pieces_by_color_from_ptr_iter(bitboard_ptr, by_piece.1.next())
-                    };
-                    for PieceWithBitboard(piece, opposing_board) in capturing_iter {
-                        let capture = board & opposing_board;
-                        if *capture != 0 {
-                            capturing = Some((piece, capture.as_bit_idx()))
-                        }
-                    }
-                }
-
-                Ply {
-                    moving_piece: by_piece,
-                    from: self.as_bit_idx(),
-                    to: board.as_bit_idx(),
-                    capturing,
-                    ..Default::default()
-                }
-            })
-    }
-
-    /// Returns a iterator of all unblocked multi-step plys (sliding pieces)
-    ///
-    /// # Safety
-    /// Will require `bitboard_ptr` to be valid until all movement generation has been done.
-    /// The pointer needs to be the Bitboards array of Bitboards
-    pub unsafe fn multi_step_plys_in_dirs(
-        &self,
-        dirs: &[fn(&Self, &Self, &Self) -> Vec<Self>],
-        blocked: &Self,
-        capturable: &Self,
-        bitboard_ptr: *const Bitboard,
-        by_piece: Piece,
-    ) -> impl Iterator<Item = Ply> {
-        dirs.iter()
-            .flat_map(|dir| dir(self, blocked, capturable))
-            .map(move |board| {
-                let mut capturing = None;
-                if *board & **capturable != 0 {
-                    // There is a capture present
-                    let capturing_iter = unsafe {
-                        all_pieces_by_color_from_ptr_iter(bitboard_ptr, by_piece.1.next())
-                    };
-                    for PieceWithBitboard(piece, opposing_board) in capturing_iter {
-                        let capture = board & opposing_board;
-                        if *capture != 0 {
-                            capturing = Some((piece, capture.as_bit_idx()))
-                        }
+    ) -> impl Iterator<Item = Ply> + use<> {
+        let capturable = *capturable;
+        let from = self.as_bit_idx();
+        targets.split().map(move |board| {
+            let mut capturing = None;
+            if *board & *capturable != 0 {
+                // There is a capture present
+                let capturing_iter =
+                    unsafe { all_pieces_by_color_from_ptr_iter(bitboard_ptr, by_piece.1.next()) };
+                for PieceWithBitboard(piece, opposing_board) in capturing_iter {
+                    let capture = board & opposing_board;
+                    if *capture != 0 {
+                        capturing = Some((piece, capture.as_bit_idx()))
                     }
                 }
+            }
 
-                Ply {
-                    moving_piece: by_piece,
-                    from: self.as_bit_idx(),
-                    to: board.as_bit_idx(),
-                    capturing,
-                    ..Default::default()
-                }
-            })
+            Ply {
+                moving_piece: by_piece,
+                from,
+                to: board.as_bit_idx(),
+                capturing,
+                ..Default::default()
+            }
+        })
     }
 }
 
 impl Bitboards {
     pub fn make_ply(&mut self, ply: &Ply) {
+        let state_before = (self.castling_pieces(), self.en_passant);
+
         // Updating moving piece
         let moving_piece_idx = bitboard_idx(ply.moving_piece);
         self.boards[moving_piece_idx].set(ply.from, false);
@@ -184,6 +173,29 @@ impl Bitboards {
             }
         }
 
+        // store state which can't be derived from the ply when unmaking it
+        let mut state = UnmakeState {
+            unmoved_pieces: self.unmoved_pieces,
+            en_passant: self.en_passant,
+            ..Default::default()
+        };
+
+        // Handle promotion, swapping the moved pawn for the promoted piece
+        if let Some(promoted_piece) = ply.promoting {
+            self.boards[moving_piece_idx].set(ply.to, false);
+            for i in 0..self.piece_list[moving_piece_idx].len() {
+                if self.piece_list[moving_piece_idx][i] == ply.to {
+                    self.piece_list[moving_piece_idx].remove(i);
+                    state.promoted_slot = i;
+                    break;
+                }
+            }
+
+            let promoted_idx = bitboard_idx(promoted_piece);
+            self.boards[promoted_idx].set(ply.to, true);
+            self.piece_list[promoted_idx].push(ply.to);
+        }
+
         // Handle capturing
         if let Some((captured_piece, idx)) = ply.capturing {
             // update position boards
@@ -194,6 +206,7 @@ impl Bitboards {
             for i in 0..self.piece_list[capturing_idx].len() {
                 if self.piece_list[capturing_idx][i] == idx {
                     self.piece_list[capturing_idx].remove(i);
+                    state.captured_slot = i;
                     break;
                 }
             }
@@ -204,6 +217,25 @@ impl Bitboards {
             let moving_piece_idx = bitboard_idx(other_piece);
             self.boards[moving_piece_idx].set(from, false);
             self.boards[moving_piece_idx].set(to, true);
+
+            for piece in self.piece_list[moving_piece_idx].iter_mut() {
+                if piece == &from {
+                    *piece = to
+                }
+            }
+        }
+
+        self.state_history.push(state);
+
+        // unmoved pieces, any tile moved from, to or captured on loses its initial piece
+        self.unmoved_pieces.set(ply.from, false);
+        self.unmoved_pieces.set(ply.to, false);
+        if let Some((_, idx)) = ply.capturing {
+            self.unmoved_pieces.set(idx, false);
+        }
+        if let Some((_, from, to)) = ply.also_move {
+            self.unmoved_pieces.set(from, false);
+            self.unmoved_pieces.set(to, false);
         }
 
         // en passant
@@ -211,28 +243,47 @@ impl Bitboards {
         self.en_passant = en_passant;
 
         // update hash
+        self.side_to_move = self.side_to_move.next();
         self.zobrist_hash = self
             .zobrist_table
             .update_hash_bitboard(self.zobrist_hash, ply);
+        self.zobrist_hash ^= self.zobrist_table.state_hash(
+            state_before.0 ^ self.castling_pieces(),
+            state_before.1 ^ self.en_passant,
+        );
+        self.pawn_hash = self.zobrist_table.update_pawn_hash(self.pawn_hash, ply);
+        #[cfg(debug_assertions)]
+        self.verify_hash();
+
+        // Positions before captures, pawn moves and lost castling rights can't repeat
+        let irreversible = ply.capturing.is_some()
+            || ply.moving_piece.0 == PieceType::Pawn
+            || state_before.0 != self.castling_pieces();
+        self.position_history
+            .push((self.zobrist_hash, irreversible));
+    }
 
-        // update visited positions
-        let mut check_cache = false;
-        self.visited_positions
-            .lock()
-            .unwrap()
-            .entry(*self.zobrist_hash)
-            .and_modify(|i| {
-                // Position already visted, checking cache
-                check_cache = true;
-                *i += 1
-            })
-            .or_insert(1);
-        self.check_quiescence_table = check_cache;
-    }
-
-    pub fn unmake_ply(&mut self, ply: &Ply, previous_ply: Option<&Ply>) {
-        // Updating moving piece
+    pub fn unmake_ply(&mut self, ply: &Ply) {
+        let state_before = (self.castling_pieces(), self.en_passant);
         let moving_piece_idx = bitboard_idx(ply.moving_piece);
+        let state = self.state_history.pop().unwrap_or_default();
+
+        // Handle promotion, swapping the promoted piece back for the pawn
+        if let Some(promoted_piece) = ply.promoting {
+            let promoted_idx = bitboard_idx(promoted_piece);
+            self.boards[promoted_idx].set(ply.to, false);
+            for i in 0..self.piece_list[promoted_idx].len() {
+                if self.piece_list[promoted_idx][i] == ply.to {
+                    self.piece_list[promoted_idx].remove(i);
+                    break;
+                }
+            }
+
+            self.boards[moving_piece_idx].set(ply.to, true);
+            self.piece_list[moving_piece_idx].insert(state.promoted_slot, ply.to);
+        }
+
+        // Updating moving piece
         self.boards[moving_piece_idx].set(ply.to, false);
         self.boards[moving_piece_idx].set(ply.from, true);
 
@@ -250,7 +301,7 @@ impl Bitboards {
             self.boards[capturing_idx].set(idx, true);
 
             // update piece list
-            self.piece_list[capturing_idx].push(idx);
+            self.piece_list[capturing_idx].insert(state.captured_slot, idx);
         }
 
         // Handle linked move
@@ -258,50 +309,75 @@ impl Bitboards {
             let moving_piece_idx = bitboard_idx(other_piece);
             self.boards[moving_piece_idx].set(to, false);
             self.boards[moving_piece_idx].set(from, true);
-        }
 
-        // restore en_passant
-        if let Some(ply) = previous_ply {
-            let en_passant = ply.en_passant_board.unwrap_or(Bitboard(u256::ZERO));
-            self.en_passant = en_passant;
-        } else {
-            self.en_passant = Bitboard(u256::ZERO);
+            for piece in self.piece_list[moving_piece_idx].iter_mut() {
+                if piece == &to {
+                    *piece = from
+                }
+            }
         }
 
-        // update visited positions
-        self.visited_positions
-            .lock()
-            .unwrap()
-            .entry(*self.zobrist_hash)
-            .and_modify(|i| *i -= 1);
+        // restore unmoved pieces and en passant
+        self.unmoved_pieces = state.unmoved_pieces;
+        self.en_passant = state.en_passant;
 
-        // returning to a previous position, so we can check cache
-        self.check_quiescence_table = true;
+        self.position_history.pop();
 
         // update hash
+        self.side_to_move = self.side_to_move.next();
         self.zobrist_hash = self
             .zobrist_table
             .update_hash_bitboard(self.zobrist_hash, ply);
+        self.zobrist_hash ^= self.zobrist_table.state_hash(
+            state_before.0 ^ self.castling_pieces(),
+            state_before.1 ^ self.en_passant,
+        );
+        self.pawn_hash = self.zobrist_table.update_pawn_hash(self.pawn_hash, ply);
+        #[cfg(debug_assertions)]
+        self.verify_hash();
     }
 
-    fn legality_check(&self, last_move_by: PieceColor) -> bool {
-        // thricefold repetiton check
-        let thricefold_repetition = self
-            .visited_positions
-            .lock()
-            .unwrap()
-            .get(&self.zobrist_hash)
-            .is_some_and(|i| *i >= 3);
-
-        if thricefold_repetition {
-            return false;
+    /// Passes the turn without moving, which clears en passant
+    pub fn make_null_ply(&mut self) {
+        self.state_history.push(UnmakeState {
+            unmoved_pieces: self.unmoved_pieces,
+            en_passant: self.en_passant,
+            ..Default::default()
+        });
+        self.zobrist_hash ^= self
+            .zobrist_table
+            .state_hash(Bitboard(u256::ZERO), self.en_passant);
+        self.en_passant = Bitboard(u256::ZERO);
+
+        self.side_to_move = self.side_to_move.next();
+        self.zobrist_hash ^= self.zobrist_table.table[CHANGE_PLAYER_INDEX];
+        #[cfg(debug_assertions)]
+        self.verify_hash();
+
+        // Positions on both sides of a passed turn don't form a repetition
+        self.position_history.push((self.zobrist_hash, true));
+    }
+
+    pub fn unmake_null_ply(&mut self) {
+        self.position_history.pop();
+
+        if let Some(state) = self.state_history.pop() {
+            self.unmoved_pieces = state.unmoved_pieces;
+            self.en_passant = state.en_passant;
         }
+        self.zobrist_hash ^= self
+            .zobrist_table
+            .state_hash(Bitboard(u256::ZERO), self.en_passant);
 
-        // king check
-        let king_mask = self.boards[bitboard_idx(Piece(PieceType::King, last_move_by))];
-        let opponent_en_prise = self.en_prise_by_color(last_move_by.next());
+        self.side_to_move = self.side_to_move.next();
+        self.zobrist_hash ^= self.zobrist_table.table[CHANGE_PLAYER_INDEX];
+        #[cfg(debug_assertions)]
+        self.verify_hash();
+    }
 
-        *king_mask & *opponent_en_prise == 0
+    fn legality_check(&self, last_move_by: PieceColor) -> bool {
+        // king check
+        !self.in_check(last_move_by)
     }
 }
 
@@ -312,15 +388,11 @@ pub fn legality_filter(
     iter.filter(move |ply| {
         boards.make_ply(ply);
         let res = boards.legality_check(ply.moving_piece.1);
-        boards.unmake_ply(ply, None);
+        boards.unmake_ply(ply);
         res
     })
 }
 
-pub fn captures_only(iter: impl Iterator<Item = Ply>) -> impl Iterator<Item = Ply> {
-    iter.filter(|ply| ply.capturing.is_some())
-}
-
 #[cfg(test)]
 mod tests {
     use std::collections::BinaryHeap;
@@ -328,16 +400,29 @@ mod tests {
     use ethnum::u256;
 
     use crate::chess_engine::{
-        bitboard::{
-            Bitboard, Bitboards, bitboard_idx,
-            move_gen::{king::KING_DIRS, queen::QUEEN_STEP_DIRS},
-        },
+        bitboard::{Bitboard, Bitboards, bitboard_idx},
         pieces::*,
         zobrist::CHANGE_PLAYER_INDEX,
     };
 
     use super::Ply;
 
+    #[test]
+    fn make_unmake_null_ply() {
+        let (mut bitboard, _) = Bitboards::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let before = bitboard.zobrist_hash;
+
+        bitboard.make_null_ply();
+        assert_eq!(*bitboard.en_passant, u256::ZERO);
+        assert_eq!(bitboard.zobrist_hash, bitboard.compute_hash());
+        assert_ne!(bitboard.zobrist_hash, before);
+
+        bitboard.unmake_null_ply();
+        assert_ne!(*bitboard.en_passant, u256::ZERO);
+        assert_eq!(bitboard.zobrist_hash, before);
+        assert!(Ply::null(PieceColor::White).is_null());
+    }
+
     #[test]
     fn single_step_plys() {
         let boards = Bitboards::new_from_str(
@@ -348,11 +433,14 @@ mod tests {
         );
         let board = boards.boards[bitboard_idx(WHITE_KING)];
 
+        let targets = board.king_move_mask(
+            &boards.blocked_mask_for_color(PieceColor::White),
+            &boards.all_pieces_by_color(PieceColor::Black),
+        );
         let mut plys = unsafe {
             board
-                .single_step_plys_in_dirs(
-                    &KING_DIRS,
-                    &boards.blocked_mask_for_color(PieceColor::White),
+                .plys_to_targets(
+                    &targets,
                     &boards.all_pieces_by_color(PieceColor::Black),
                     boards.boards.as_ptr(),
                     WHITE_KING,
@@ -375,11 +463,14 @@ mod tests {
         );
         let board = boards.boards[bitboard_idx(WHITE_QUEEN)];
 
+        let targets = board.queen_move_mask(
+            &boards.blocked_mask_for_color(PieceColor::White),
+            &boards.all_pieces_by_color(PieceColor::Black),
+        );
         let mut plys = unsafe {
             board
-                .multi_step_plys_in_dirs(
-                    &QUEEN_STEP_DIRS,
-                    &boards.blocked_mask_for_color(PieceColor::White),
+                .plys_to_targets(
+                    &targets,
                     &boards.all_pieces_by_color(PieceColor::Black),
                     boards.boards.as_ptr(),
                     WHITE_QUEEN,
@@ -427,6 +518,12 @@ mod tests {
             ..Default::default()
         };
 
+        let queen_killer = Ply {
+            moving_piece: WHITE_QUEEN,
+            order_score: 2,
+            ..Default::default()
+        };
+
         let mut vec = vec![
             pawn_takes_pawn,
             pawn_takes_queen,
@@ -434,6 +531,7 @@ mod tests {
             queen_takes_queen,
             queen_no_take,
             pawn_no_take,
+            queen_killer,
         ];
         vec.sort();
         vec.reverse();
@@ -445,6 +543,7 @@ mod tests {
                 queen_takes_queen,
                 pawn_takes_pawn,
                 queen_takes_pawn,
+                queen_killer,
                 pawn_no_take,
                 queen_no_take,
             ]
@@ -498,7 +597,7 @@ mod tests {
         };
 
         bitboard.make_ply(&ply);
-        bitboard.unmake_ply(&ply, None);
+        bitboard.unmake_ply(&ply);
         assert_eq!(bitboard, expected);
     }
 
@@ -535,8 +634,8 @@ mod tests {
     fn unmake_capture_ply() {
         let mut bitboard = Bitboards::new_from_str(
             r#"
-        0
-        p
+        0P
+        p0
         "#,
         );
 
@@ -551,7 +650,7 @@ mod tests {
         };
 
         bitboard.make_ply(&ply);
-        bitboard.unmake_ply(&ply, None);
+        bitboard.unmake_ply(&ply);
         assert_eq!(bitboard, expected);
     }
 
@@ -604,7 +703,7 @@ mod tests {
         };
 
         bitboard.make_ply(&ply);
-        bitboard.unmake_ply(&ply, None);
+        bitboard.unmake_ply(&ply);
         assert_eq!(bitboard.en_passant, Bitboard(u256::ZERO));
     }
 
@@ -644,10 +743,134 @@ mod tests {
 
         bitboard.make_ply(&first_ply);
         bitboard.make_ply(&second_ply);
-        bitboard.unmake_ply(&second_ply, Some(&first_ply));
+        bitboard.unmake_ply(&second_ply);
         assert_eq!(bitboard.en_passant, expected);
     }
 
+    #[test]
+    fn make_promotion_ply() {
+        let mut bitboard = Bitboards::new_from_str(
+            r#"
+        0R
+        p0
+        "#,
+        );
+
+        let ply = Ply {
+            moving_piece: WHITE_PAWN,
+            from: 16.into(),
+            to: 1.into(),
+            capturing: Some((BLACK_ROOK, 1.into())),
+            promoting: Some(WHITE_KNIGHT),
+            ..Default::default()
+        };
+
+        bitboard.make_ply(&ply);
+        assert_eq!(*bitboard.boards[bitboard_idx(WHITE_PAWN)], 0);
+        assert_eq!(*bitboard.boards[bitboard_idx(BLACK_ROOK)], 0);
+        assert_eq!(
+            bitboard.boards[bitboard_idx(WHITE_KNIGHT)],
+            Bitboard(u256::ONE << 1)
+        );
+        assert!(bitboard.piece_list[bitboard_idx(WHITE_PAWN)].is_empty());
+        assert_eq!(
+            bitboard.piece_list[bitboard_idx(WHITE_KNIGHT)],
+            vec![1.into()]
+        );
+    }
+
+    #[test]
+    fn unmake_promotion_ply() {
+        let mut bitboard = Bitboards::new_from_str(
+            r#"
+        0R
+        p0
+        "#,
+        );
+
+        let expected = bitboard.clone();
+
+        let ply = Ply {
+            moving_piece: WHITE_PAWN,
+            from: 16.into(),
+            to: 1.into(),
+            capturing: Some((BLACK_ROOK, 1.into())),
+            promoting: Some(WHITE_QUEEN),
+            ..Default::default()
+        };
+
+        bitboard.make_ply(&ply);
+        bitboard.unmake_ply(&ply);
+        assert_eq!(bitboard, expected);
+        assert_eq!(bitboard.boards, expected.boards);
+        assert_eq!(bitboard.piece_list, expected.piece_list);
+    }
+
+    #[test]
+    fn make_castling_ply() {
+        let mut bitboard = Bitboards::new_from_str(
+            r#"
+        0000
+        k00r
+        "#,
+        );
+
+        let mut expected = Bitboards::new_from_str(
+            r#"
+        0000
+        0rk0
+        "#,
+        );
+        // king and rook lost their castling rights
+        expected.unmoved_pieces = Bitboard(u256::ZERO);
+        expected.side_to_move = PieceColor::Black;
+        expected.zobrist_hash = expected.compute_hash();
+
+        let ply = Ply {
+            moving_piece: WHITE_KING,
+            from: 16.into(),
+            to: 18.into(),
+            also_move: Some((WHITE_ROOK, 19.into(), 17.into())),
+            ..Default::default()
+        };
+
+        bitboard.make_ply(&ply);
+        assert_eq!(bitboard, expected);
+        assert_eq!(bitboard.boards, expected.boards);
+        assert_eq!(
+            bitboard.piece_list[bitboard_idx(WHITE_ROOK)],
+            vec![17.into()]
+        );
+        assert_eq!(*bitboard.unmoved_pieces, 0);
+    }
+
+    #[test]
+    fn unmake_castling_ply() {
+        let mut bitboard = Bitboards::new_from_str(
+            r#"
+        0000
+        k00r
+        "#,
+        );
+
+        let expected = bitboard.clone();
+
+        let ply = Ply {
+            moving_piece: WHITE_KING,
+            from: 16.into(),
+            to: 18.into(),
+            also_move: Some((WHITE_ROOK, 19.into(), 17.into())),
+            ..Default::default()
+        };
+
+        bitboard.make_ply(&ply);
+        bitboard.unmake_ply(&ply);
+        assert_eq!(bitboard, expected);
+        assert_eq!(bitboard.boards, expected.boards);
+        assert_eq!(bitboard.piece_list, expected.piece_list);
+        assert_eq!(bitboard.unmoved_pieces, expected.unmoved_pieces);
+    }
+
     #[test]
     fn make_ply_visited_count() {
         let mut bitboard = Bitboards::new_from_str(
@@ -665,14 +888,55 @@ mod tests {
         };
 
         bitboard.make_ply(&ply);
+        assert_eq!(bitboard.repetition_count(), 1);
         assert_eq!(
-            bitboard
-                .visited_positions
-                .lock()
-                .unwrap()
-                .get(&bitboard.zobrist_hash),
-            Some(&1)
+            bitboard.position_history.last(),
+            Some(&(bitboard.zobrist_hash, true))
+        );
+    }
+
+    #[test]
+    fn repetition_count_since_irreversible_ply() {
+        let mut bitboard = Bitboards::new_from_str(
+            r#"
+        00
+        n0
+        0p
+        "#,
         );
+
+        let forth = Ply {
+            moving_piece: WHITE_KNIGHT,
+            from: 16.into(),
+            to: 1.into(),
+            ..Default::default()
+        };
+        let back = Ply {
+            from: 1.into(),
+            to: 16.into(),
+            ..forth
+        };
+        for _ in 0..2 {
+            bitboard.make_ply(&forth);
+            bitboard.make_ply(&back);
+        }
+        assert_eq!(bitboard.repetition_count(), 3);
+
+        let push = Ply {
+            moving_piece: WHITE_PAWN,
+            from: 33.into(),
+            to: 17.into(),
+            ..Default::default()
+        };
+        bitboard.make_ply(&push);
+        bitboard.make_ply(&forth);
+        bitboard.make_ply(&back);
+        assert_eq!(bitboard.repetition_count(), 2);
+
+        bitboard.unmake_ply(&back);
+        bitboard.unmake_ply(&forth);
+        bitboard.unmake_ply(&push);
+        assert_eq!(bitboard.repetition_count(), 3);
     }
 
     #[test]
@@ -693,11 +957,14 @@ mod tests {
 
         bitboard.make_ply(&ply);
         let hash = bitboard.zobrist_hash;
-        bitboard.unmake_ply(&ply, None);
+        bitboard.unmake_ply(&ply);
 
-        assert_eq!(
-            bitboard.visited_positions.lock().unwrap().get(&hash),
-            Some(&0)
+        assert_eq!(bitboard.position_history.len(), 1);
+        assert!(
+            bitboard
+                .position_history
+                .iter()
+                .all(|(visited, _)| *visited != hash)
         );
     }
 
@@ -782,11 +1049,39 @@ mod tests {
         };
 
         bitboard.make_ply(&ply);
-        bitboard.unmake_ply(&ply, None);
+        bitboard.unmake_ply(&ply);
         let bitboard_idx = bitboard_idx(WHITE_PAWN);
         assert_eq!(bitboard.piece_list[bitboard_idx], vec![16.into()]);
     }
 
+    #[test]
+    fn unmake_ply_keeps_piece_list_order() {
+        let (mut bitboard, _) = Bitboards::from_fen("k7/1P6/8/8/8/8/8/PrPPPPPK w - - 0 1").unwrap();
+        let pawns = bitboard.piece_list[bitboard_idx(WHITE_PAWN)].clone();
+
+        // neither the captured nor the promoting pawn are last in the list
+        let capture = Ply {
+            moving_piece: BLACK_ROOK,
+            from: 113.into(),
+            to: 112.into(),
+            capturing: Some((WHITE_PAWN, 112.into())),
+            ..Default::default()
+        };
+        let promotion = Ply {
+            moving_piece: WHITE_PAWN,
+            from: 17.into(),
+            to: 1.into(),
+            promoting: Some(WHITE_QUEEN),
+            ..Default::default()
+        };
+
+        bitboard.make_ply(&capture);
+        bitboard.make_ply(&promotion);
+        bitboard.unmake_ply(&promotion);
+        bitboard.unmake_ply(&capture);
+        assert_eq!(bitboard.piece_list[bitboard_idx(WHITE_PAWN)], pawns);
+    }
+
     #[test]
     fn display_ply() {
         let ply = Ply {
@@ -799,6 +1094,19 @@ mod tests {
         assert_eq!(ply.to_string().as_str(), "p A2A1");
     }
 
+    #[test]
+    fn display_promoting_ply() {
+        let ply = Ply {
+            moving_piece: WHITE_PAWN,
+            from: 16.into(),
+            to: 0.into(),
+            promoting: Some(WHITE_QUEEN),
+            ..Default::default()
+        };
+
+        assert_eq!(ply.to_string().as_str(), "p A2A1 =q");
+    }
+
     #[test]
     fn display_capturing_ply() {
         let ply = Ply {
@@ -1,33 +1,15 @@
 use crate::chess_engine::{bitboard::Bitboard, pieces::Piece};
 
-use super::ply::Ply;
-
-pub(crate) const QUEEN_STEP_DIRS: [fn(&Bitboard, &Bitboard, &Bitboard) -> Vec<Bitboard>; 8] = [
-    Bitboard::step_we,
-    Bitboard::step_nw,
-    Bitboard::step_no,
-    Bitboard::step_ne,
-    Bitboard::step_ea,
-    Bitboard::step_se,
-    Bitboard::step_so,
-    Bitboard::step_sw,
-];
+use super::{
+    attacks::{BISHOP_RAYS, ROOK_RAYS},
+    ply::Ply,
+};
 
 impl Bitboard {
     /// Cumulative pseudolegal mask of queen moves
     pub fn queen_move_mask(&self, blocked: &Bitboard, capturable: &Bitboard) -> Self {
-        let dirs = [
-            Self::fill_we,
-            Self::fill_nw,
-            Self::fill_no,
-            Self::fill_ne,
-            Self::fill_ea,
-            Self::fill_se,
-            Self::fill_so,
-            Self::fill_sw,
-        ];
-
-        self.fill_in_dirs(&dirs, blocked, capturable)
+        self.sliding_attacks(&ROOK_RAYS, blocked, capturable)
+            | self.sliding_attacks(&BISHOP_RAYS, blocked, capturable)
     }
 
     /// Mask of threatened positions
@@ -45,7 +27,8 @@ impl Bitboard {
         piece: Piece,
     ) -> impl Iterator<Item = Ply> {
         unsafe {
-            self.multi_step_plys_in_dirs(&QUEEN_STEP_DIRS, blocked, capturable, bitboard_ptr, piece)
+            let targets = self.queen_move_mask(blocked, capturable);
+            self.plys_to_targets(&targets, capturable, bitboard_ptr, piece)
         }
     }
 }
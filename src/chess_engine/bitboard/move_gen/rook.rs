@@ -1,19 +1,11 @@
 use crate::chess_engine::{bitboard::Bitboard, pieces::Piece};
 
-use super::ply::Ply;
-
-const ROOK_STEP_DIRS: [fn(&Bitboard, &Bitboard, &Bitboard) -> Vec<Bitboard>; 4] = [
-    Bitboard::step_we,
-    Bitboard::step_no,
-    Bitboard::step_ea,
-    Bitboard::step_so,
-];
+use super::{attacks::ROOK_RAYS, ply::Ply};
 
 impl Bitboard {
     /// Cumulative pseudolegal  mask of rook moves (no castling)
     pub fn rook_move_mask(&self, blocked: &Bitboard, capturable: &Bitboard) -> Self {
-        let dirs = [Self::fill_we, Self::fill_no, Self::fill_ea, Self::fill_so];
-        self.fill_in_dirs(&dirs, blocked, capturable)
+        self.sliding_attacks(&ROOK_RAYS, blocked, capturable)
     }
 
     /// Mask of threatened positions
@@ -31,7 +23,8 @@ impl Bitboard {
         piece: Piece,
     ) -> impl Iterator<Item = Ply> {
         unsafe {
-            self.multi_step_plys_in_dirs(&ROOK_STEP_DIRS, blocked, capturable, bitboard_ptr, piece)
+            let targets = self.rook_move_mask(blocked, capturable);
+            self.plys_to_targets(&targets, capturable, bitboard_ptr, piece)
         }
     }
 }
@@ -0,0 +1,351 @@
+//! Long algebraic (`e2e4`, `e7e8q`) and standard algebraic (`Nxf3+`, `O-O`) notation.
+//! Tile names follow `Bitboards::square_name`, so ranks count from the bottom of the active area.
+
+use crate::chess_engine::pieces::{Piece, PieceColor, PieceType};
+
+use super::{Bitboards, Ply};
+
+impl Bitboards {
+    /// Long algebraic notation of `ply`, as used by UCI
+    pub fn ply_to_long_algebraic(&self, ply: &Ply) -> String {
+        let mut notation = self.square_name(ply.from) + &self.square_name(ply.to);
+        if let Some(piece) = ply.promoting {
+            notation.push(piece.as_char().to_ascii_lowercase());
+        }
+        notation
+    }
+
+    /// Finds the legal ply of `color` matching the long algebraic `notation`
+    pub fn ply_from_long_algebraic(&mut self, color: PieceColor, notation: &str) -> Option<Ply> {
+        let notation = notation.trim().to_ascii_lowercase();
+        self.all_legal_plys_by_color::<Vec<Ply>>(color)
+            .into_iter()
+            .find(|ply| self.ply_to_long_algebraic(ply) == notation)
+    }
+
+    /// Standard algebraic notation of `ply`, which has to be legal in the current position
+    pub fn ply_to_san(&mut self, ply: &Ply) -> String {
+        let Piece(piece_type, color) = ply.moving_piece;
+        let mut san = if let Some((_, rook_from, _)) = ply.also_move {
+            // Castling, named by the side the rook stands on
+            if *rook_from % 16 > *ply.from % 16 {
+                "O-O".to_string()
+            } else {
+                "O-O-O".to_string()
+            }
+        } else {
+            let from = self.square_name(ply.from);
+            let (from_file, from_rank) = from.split_at(1);
+            let mut san = String::new();
+
+            if piece_type == PieceType::Pawn {
+                if ply.capturing.is_some() {
+                    san.push_str(from_file);
+                }
+            } else {
+                san.push(Piece(piece_type, PieceColor::White).as_fen_char());
+
+                // Disambiguate between pieces of the same type reaching the same tile
+                let rivals: Vec<String> = self
+                    .all_legal_plys_by_color::<Vec<Ply>>(color)
+                    .into_iter()
+                    .filter(|other| {
+                        other.moving_piece == ply.moving_piece
+                            && other.to == ply.to
+                            && other.from != ply.from
+                    })
+                    .map(|other| self.square_name(other.from))
+                    .collect();
+                if !rivals.is_empty() {
+                    if rivals.iter().all(|rival| !rival.starts_with(from_file)) {
+                        san.push_str(from_file);
+                    } else if rivals.iter().all(|rival| &rival[1..] != from_rank) {
+                        san.push_str(from_rank);
+                    } else {
+                        san.push_str(&from);
+                    }
+                }
+            }
+
+            if ply.capturing.is_some() {
+                san.push('x');
+            }
+            san.push_str(&self.square_name(ply.to));
+
+            if let Some(piece) = ply.promoting {
+                san.push('=');
+                san.push(piece.as_char().to_ascii_uppercase());
+            }
+            san
+        };
+
+        // Check and checkmate markers
+        self.make_ply(ply);
+        if self.in_check(color.next()) {
+            let mated = self
+                .all_legal_plys_by_color::<Vec<Ply>>(color.next())
+                .is_empty();
+            san.push(if mated { '#' } else { '+' });
+        }
+        self.unmake_ply(ply);
+
+        san
+    }
+
+    /// Finds the legal ply of `color` matching the standard algebraic `notation`.
+    /// Check markers, annotations and the `=` of promotions are optional, as is a disambiguation
+    /// beyond what is needed. The notation is split into its fields, which the legal plys are
+    /// matched against, so no ply has to be made.
+    pub fn ply_from_san(&mut self, color: PieceColor, notation: &str) -> Option<Ply> {
+        let notation = notation.trim();
+        // castling is sometimes written with zeros
+        let notation = if notation.starts_with("0-0") {
+            notation.replace('0', "O")
+        } else {
+            notation.to_string()
+        };
+        let notation: String = notation
+            .chars()
+            .filter(|char| !matches!(char, '+' | '#' | '!' | '?' | '='))
+            .collect();
+        if !notation.is_ascii() {
+            return None;
+        }
+        let plys = self.all_legal_plys_by_color::<Vec<Ply>>(color);
+
+        // Castling, named by the side the rook stands on
+        if notation == "O-O" || notation == "O-O-O" {
+            let kingside = notation == "O-O";
+            return plys.into_iter().find(|ply| {
+                ply.also_move
+                    .is_some_and(|(_, rook_from, _)| (*rook_from % 16 > *ply.from % 16) == kingside)
+            });
+        }
+
+        // Promotion piece at the end, moving piece at the start, pawns go without a letter
+        let mut rest = notation.as_str();
+        let promoting = match rest.chars().last()? {
+            char if char.is_ascii_uppercase() => {
+                rest = &rest[..rest.len() - 1];
+                Some(Piece::from_fen_char(char)?.0)
+            }
+            _ => None,
+        };
+        let piece_type = match rest.chars().next()? {
+            char if char.is_ascii_uppercase() => {
+                rest = &rest[1..];
+                Piece::from_fen_char(char)?.0
+            }
+            _ => PieceType::Pawn,
+        };
+
+        // Destination tile, preceded by the capture marker and the tile, file or rank moved from
+        let to_start = rest
+            .trim_end_matches(|char: char| char.is_ascii_digit())
+            .len()
+            .checked_sub(1)?;
+        let to = self.parse_square(&rest[to_start..])?;
+        rest = &rest[..to_start];
+        let (from_hint, capturing) = match rest.strip_suffix('x') {
+            Some(from_hint) => (from_hint, true),
+            None => (rest, false),
+        };
+
+        let mut candidates = plys.into_iter().filter(|ply| {
+            let from = self.square_name(ply.from);
+            let (from_file, from_rank) = from.split_at(1);
+            ply.moving_piece.0 == piece_type
+                && ply.to == to
+                && ply.also_move.is_none()
+                && ply.capturing.is_some() == capturing
+                && ply.promoting.map(|piece| piece.0) == promoting
+                && (from_hint.is_empty()
+                    || from_hint == from
+                    || from_hint == from_file
+                    || from_hint == from_rank)
+        });
+
+        // A notation matching more than one ply is ambiguous
+        let ply = candidates.next()?;
+        candidates.next().is_none().then_some(ply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chess_engine::{bitboard::STARTING_FEN, pieces::*};
+
+    use super::*;
+
+    fn find_ply(boards: &mut Bitboards, color: PieceColor, from: &str, to: &str) -> Ply {
+        let from = boards.parse_square(from).unwrap();
+        let to = boards.parse_square(to).unwrap();
+        boards
+            .all_legal_plys_by_color::<Vec<Ply>>(color)
+            .into_iter()
+            .find(|ply| ply.from == from && ply.to == to)
+            .unwrap()
+    }
+
+    #[test]
+    fn long_algebraic_round_trip() {
+        let (mut boards, _) = Bitboards::from_fen(STARTING_FEN).unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "e2", "e4");
+        assert_eq!(boards.ply_to_long_algebraic(&ply), "e2e4");
+        assert_eq!(
+            boards.ply_from_long_algebraic(PieceColor::White, "e2e4"),
+            Some(ply)
+        );
+        assert_eq!(
+            boards.ply_from_long_algebraic(PieceColor::White, "e2e5"),
+            None
+        );
+    }
+
+    #[test]
+    fn long_algebraic_promotion() {
+        let (mut boards, _) = Bitboards::from_fen("7k/4P3/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let ply = boards
+            .ply_from_long_algebraic(PieceColor::White, "e7e8n")
+            .unwrap();
+        assert_eq!(ply.promoting, Some(WHITE_KNIGHT));
+        assert_eq!(boards.ply_to_long_algebraic(&ply), "e7e8n");
+    }
+
+    #[test]
+    fn san_pawn_and_piece_plys() {
+        let (mut boards, _) = Bitboards::from_fen(STARTING_FEN).unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "e2", "e4");
+        assert_eq!(boards.ply_to_san(&ply), "e4");
+        let ply = find_ply(&mut boards, PieceColor::White, "g1", "f3");
+        assert_eq!(boards.ply_to_san(&ply), "Nf3");
+    }
+
+    #[test]
+    fn san_captures() {
+        let (mut boards, _) = Bitboards::from_fen("4k3/8/8/3p4/4P3/5n2/8/K5N1 w - - 0 1").unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "e4", "d5");
+        assert_eq!(boards.ply_to_san(&ply), "exd5");
+        let ply = find_ply(&mut boards, PieceColor::White, "g1", "f3");
+        assert_eq!(boards.ply_to_san(&ply), "Nxf3");
+        let ply = find_ply(&mut boards, PieceColor::Black, "f3", "g1");
+        assert_eq!(boards.ply_to_san(&ply), "Nxg1");
+    }
+
+    #[test]
+    fn san_check_and_mate_markers() {
+        let (mut boards, _) = Bitboards::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "a1", "a8");
+        assert_eq!(boards.ply_to_san(&ply), "Ra8#");
+
+        let (mut boards, _) = Bitboards::from_fen("6k1/8/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "a1", "a8");
+        assert_eq!(boards.ply_to_san(&ply), "Ra8+");
+    }
+
+    #[test]
+    fn san_disambiguation() {
+        // Knights on different files
+        let (mut boards, _) = Bitboards::from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "f1", "d2");
+        assert_eq!(boards.ply_to_san(&ply), "Nfd2");
+
+        // Rooks on the same rank
+        let (mut boards, _) = Bitboards::from_fen("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "a1", "d1");
+        assert_eq!(boards.ply_to_san(&ply), "Rad1");
+
+        // Rooks on the same file
+        let (mut boards, _) = Bitboards::from_fen("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "a1", "a4");
+        assert_eq!(boards.ply_to_san(&ply), "R1a4");
+
+        // Queens sharing both file and rank with rivals
+        let (mut boards, _) = Bitboards::from_fen("4k3/8/8/8/Q1Q5/8/Q7/4K3 w - - 0 1").unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "a4", "b3");
+        assert_eq!(boards.ply_to_san(&ply), "Qa4b3");
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Qa4b3"), Some(ply));
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Qab3"), None);
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Q4b3"), None);
+    }
+
+    #[test]
+    fn san_parsing_is_strict_about_fields() {
+        let (mut boards, _) =
+            Bitboards::from_fen("4k3/8/8/3p4/4P3/5N2/8/1N2K2R w K - 0 1").unwrap();
+        // missing or extra capture marker
+        assert_eq!(boards.ply_from_san(PieceColor::White, "ed5"), None);
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Nxd2"), None);
+        // ambiguous between the knights, unless disambiguated
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Nd2"), None);
+        let ply = find_ply(&mut boards, PieceColor::White, "b1", "d2");
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Nbd2"), Some(ply));
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Nb1d2"), Some(ply));
+        // castling is only found by its own notation
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Kg1"), None);
+        let ply = boards.ply_from_san(PieceColor::White, "O-O").unwrap();
+        assert!(ply.also_move.is_some());
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Zd2"), None);
+        assert_eq!(boards.ply_from_san(PieceColor::White, "Nd"), None);
+    }
+
+    #[test]
+    fn san_castling() {
+        let (mut boards, _) = Bitboards::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "e1", "g1");
+        assert_eq!(boards.ply_to_san(&ply), "O-O");
+        let ply = find_ply(&mut boards, PieceColor::White, "e1", "c1");
+        assert_eq!(boards.ply_to_san(&ply), "O-O-O");
+        assert_eq!(boards.ply_from_san(PieceColor::White, "0-0-0"), Some(ply));
+    }
+
+    #[test]
+    fn san_promotion() {
+        let (mut boards, _) = Bitboards::from_fen("3k4/4P3/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let ply = boards.ply_from_san(PieceColor::White, "e8=Q+").unwrap();
+        assert_eq!(ply.promoting, Some(WHITE_QUEEN));
+        assert_eq!(boards.ply_to_san(&ply), "e8=Q+");
+        assert_eq!(boards.ply_from_san(PieceColor::White, "e8R"), {
+            let rook = Ply {
+                promoting: Some(WHITE_ROOK),
+                ..ply
+            };
+            Some(rook)
+        });
+    }
+
+    #[test]
+    fn san_ranks_follow_active_area() {
+        // 10 ranks tall, so white pawns start on the second of ten ranks
+        let (mut boards, _) =
+            Bitboards::from_fen("rnbqkbnr/pppppppp/8/8/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1")
+                .unwrap();
+        let ply = find_ply(&mut boards, PieceColor::White, "e2", "e4");
+        assert_eq!(boards.ply_to_san(&ply), "e4");
+        assert_eq!(boards.ply_to_long_algebraic(&ply), "e2e4");
+        let ply = find_ply(&mut boards, PieceColor::Black, "e9", "e7");
+        assert_eq!(boards.ply_to_long_algebraic(&ply), "e9e7");
+    }
+
+    #[test]
+    fn san_round_trip_all_legal_plys() {
+        let (mut boards, _) = Bitboards::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        for ply in boards.all_legal_plys_by_color::<Vec<Ply>>(PieceColor::White) {
+            let san = boards.ply_to_san(&ply);
+            assert_eq!(
+                boards.ply_from_san(PieceColor::White, &san),
+                Some(ply),
+                "{}",
+                san
+            );
+            let lan = boards.ply_to_long_algebraic(&ply);
+            assert_eq!(
+                boards.ply_from_long_algebraic(PieceColor::White, &lan),
+                Some(ply)
+            );
+        }
+    }
+}
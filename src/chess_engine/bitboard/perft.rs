@@ -0,0 +1,184 @@
+//! Move generation validation by counting leaf nodes of the legal move tree.
+//! `perft_checked` additionally verifies every `make_ply`/`unmake_ply` pair to restore the position
+//! exactly, in any build profile.
+
+use std::fmt::Debug;
+
+use crate::chess_engine::pieces::PieceColor;
+
+use super::{Bitboards, MoveList, Ply};
+
+impl Bitboards {
+    /// Number of leaf nodes of the legal move tree `depth` plys deep
+    pub fn perft(&mut self, side_to_move: PieceColor, depth: u8) -> u64 {
+        self.perft_nodes::<false>(side_to_move, depth)
+    }
+
+    /// Like `perft`, but panics once unmaking a ply doesn't restore the exact state it was made in
+    pub fn perft_checked(&mut self, side_to_move: PieceColor, depth: u8) -> u64 {
+        self.perft_nodes::<true>(side_to_move, depth)
+    }
+
+    fn perft_nodes<const CHECKED: bool>(&mut self, side_to_move: PieceColor, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let plys = self.all_legal_plys_by_color::<MoveList>(side_to_move);
+        if depth == 1 {
+            return plys.len() as u64;
+        }
+
+        plys.iter()
+            .map(|ply| self.perft_child::<CHECKED>(ply, side_to_move, depth))
+            .sum()
+    }
+
+    /// Leaf node count per legal ply at the root, for comparison with reference engines
+    pub fn divide(&mut self, side_to_move: PieceColor, depth: u8) -> Vec<(Ply, u64)> {
+        if depth == 0 {
+            return vec![];
+        }
+
+        self.all_legal_plys_by_color::<Vec<Ply>>(side_to_move)
+            .iter()
+            .map(|ply| (*ply, self.perft_child::<false>(ply, side_to_move, depth)))
+            .collect()
+    }
+
+    fn perft_child<const CHECKED: bool>(
+        &mut self,
+        ply: &Ply,
+        side_to_move: PieceColor,
+        depth: u8,
+    ) -> u64 {
+        let snapshot = CHECKED.then(|| self.perft_snapshot());
+
+        self.make_ply(ply);
+        let nodes = self.perft_nodes::<CHECKED>(side_to_move.next(), depth - 1);
+        self.unmake_ply(ply);
+
+        if let Some(snapshot) = snapshot {
+            assert_eq!(
+                self.perft_snapshot(),
+                snapshot,
+                "make/unmake of {} did not restore the position",
+                ply
+            );
+        }
+
+        nodes
+    }
+
+    /// All state a make/unmake round trip has to restore, piece lists in their exact order
+    fn perft_snapshot(&self) -> impl PartialEq + Debug + use<> {
+        (
+            self.boards,
+            self.piece_list.clone(),
+            self.zobrist_hash,
+            self.pawn_hash,
+            self.unmoved_pieces,
+            self.en_passant,
+            self.side_to_move,
+            self.state_history.clone(),
+            self.position_history.clone(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chess_engine::bitboard::STARTING_FEN;
+
+    use super::*;
+
+    /// Checks the node counts for depth 1 and onwards
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let (mut boards, info) = Bitboards::from_fen(fen).unwrap();
+        for (depth, &nodes) in expected.iter().enumerate() {
+            assert_eq!(
+                boards.perft_checked(info.side_to_move, depth as u8 + 1),
+                nodes,
+                "depth {} of {}",
+                depth + 1,
+                fen
+            );
+        }
+        // all tree walks have been undone
+        assert_eq!(boards.to_fen(&info), fen);
+    }
+
+    #[test]
+    fn perft_depth_zero() {
+        let (mut boards, _) = Bitboards::from_fen(STARTING_FEN).unwrap();
+        assert_eq!(boards.perft(PieceColor::White, 0), 1);
+        assert!(boards.divide(PieceColor::White, 0).is_empty());
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn perft_starting_position() {
+        assert_perft(STARTING_FEN, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn perft_kiwipete() {
+        // castling, en passant, promotions and pins
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        );
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn perft_position_3() {
+        // en passant discovering a check along the rank
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812, 43238],
+        );
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn perft_position_4() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn perft_position_5() {
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        );
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn perft_position_6() {
+        assert_perft(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[46, 2079, 89890],
+        );
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn divide_sums_to_perft() {
+        let (mut boards, _) = Bitboards::from_fen(STARTING_FEN).unwrap();
+        let divide = boards.divide(PieceColor::White, 3);
+        assert_eq!(divide.len(), 20);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+
+        let e4 = boards
+            .ply_from_long_algebraic(PieceColor::White, "e2e4")
+            .unwrap();
+        assert!(divide.contains(&(e4, 600)));
+    }
+}
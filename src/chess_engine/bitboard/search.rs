@@ -1,15 +1,77 @@
-use rand::{Rng, rng};
-use strum::IntoEnumIterator;
-
 use crate::chess_engine::{
-    bitboard::Ply,
-    pieces::{BLACK_PAWN, Piece, PieceColor, PieceType},
+    bitboard::{MoveList, Ply},
+    pieces::{Piece, PieceColor, PieceType},
+};
+use std::{
+    error::Error,
+    fmt::Display,
+    str::FromStr,
+    time::{Duration, Instant},
 };
-use std::collections::BinaryHeap;
 
 use super::{Bitboards, bitboard_idx};
 
-#[derive(Debug)]
+mod config;
+pub use config::SearchConfig;
+
+mod evaluator;
+pub use evaluator::{ClassicalEvaluator, Evaluator};
+
+mod heuristics;
+use heuristics::{History, Killers};
+
+mod king_safety;
+
+mod limits;
+pub use limits::{Clock, SearchLimits, StopFlag};
+
+mod move_picker;
+use move_picker::MovePicker;
+
+mod pawn_structure;
+pub(super) use pawn_structure::PawnTable;
+
+mod piece_square;
+pub use piece_square::SquareWeights;
+
+mod see;
+
+mod transposition;
+pub use transposition::{Bound, PlyKey, TranspositionEntry, TranspositionTable};
+use transposition::{score_from_table, score_to_table};
+
+mod tuning;
+pub use tuning::{LabelError, LabelledPosition, Tuner, TuningReport};
+
+/// Score of a checkmate at the root, reduced by one per ply until the mate
+pub const MATE_SCORE: i32 = 1_000_000;
+/// Scores within this distance of `MATE_SCORE` denote a forced mate
+const MAX_MATE_DISTANCE: i32 = 1_000;
+/// Deepest iteration of a search without a depth limit
+pub const MAX_DEPTH: i8 = 64;
+/// Visited nodes between two checks of the clock
+const TIME_CHECK_INTERVAL: u64 = 1024;
+/// Remaining depth from which null move pruning is tried
+const NULL_MOVE_MIN_DEPTH: i8 = 3;
+/// Depth reduction of the search after a null move, on top of the passed ply
+const NULL_MOVE_REDUCTION: i8 = 2;
+/// Remaining depth from which late plys are reduced
+const LMR_MIN_DEPTH: i8 = 3;
+/// Plys searched at full depth before reductions apply
+const LMR_FULL_DEPTH_PLYS: usize = 3;
+/// Stack size of searching threads, every node keeps its move list on the stack
+pub const SEARCH_STACK_SIZE: usize = 64 << 20;
+
+/// Plys until the side to move mates (positive) or gets mated (negative), if `score` is a mate score
+pub fn mate_distance(score: i32) -> Option<i32> {
+    if score.abs() > MATE_SCORE - MAX_MATE_DISTANCE {
+        Some(score.signum() * (MATE_SCORE - score.abs()))
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Weights {
     // Material weights
     pub king: i32,
@@ -21,7 +83,24 @@ pub struct Weights {
 
     // Strategic weights
     pub isolated_pawn: i32,
+    pub doubled_pawn: i32,
+    pub backward_pawn: i32,
+    pub connected_pawn: i32,
+    pub passed_pawn: i32,
+    /// Passed pawn bonus scaled by the ranks advanced, reaching the full weight on the promotion rank
+    pub passed_pawn_advance: i32,
+    pub king_shield: i32,
+    pub king_open_file: i32,
+    pub king_zone_attack: i32,
     pub movement: i32,
+
+    // Piece-square weights
+    pub king_square: SquareWeights,
+    pub queen_square: SquareWeights,
+    pub rook_square: SquareWeights,
+    pub bishop_square: SquareWeights,
+    pub knight_square: SquareWeights,
+    pub pawn_square: SquareWeights,
 }
 
 impl Default for Weights {
@@ -34,147 +113,385 @@ impl Default for Weights {
             knight: 60,
             pawn: 20,
             isolated_pawn: -5,
+            doubled_pawn: -8,
+            backward_pawn: -6,
+            connected_pawn: 4,
+            passed_pawn: 10,
+            passed_pawn_advance: 40,
+            king_shield: 8,
+            king_open_file: -12,
+            king_zone_attack: -6,
             movement: 1,
+            king_square: SquareWeights {
+                centre_mg: -10,
+                centre_eg: 15,
+                advance_mg: -15,
+                advance_eg: 0,
+            },
+            queen_square: SquareWeights {
+                centre_mg: 4,
+                centre_eg: 6,
+                advance_mg: 0,
+                advance_eg: 0,
+            },
+            rook_square: SquareWeights {
+                centre_mg: 2,
+                centre_eg: 0,
+                advance_mg: 5,
+                advance_eg: 0,
+            },
+            bishop_square: SquareWeights {
+                centre_mg: 8,
+                centre_eg: 5,
+                advance_mg: 0,
+                advance_eg: 0,
+            },
+            knight_square: SquareWeights {
+                centre_mg: 15,
+                centre_eg: 10,
+                advance_mg: 0,
+                advance_eg: 0,
+            },
+            pawn_square: SquareWeights {
+                centre_mg: 6,
+                centre_eg: 0,
+                advance_mg: 10,
+                advance_eg: 30,
+            },
         }
     }
 }
 
+impl Weights {
+    /// Names of all weights, in declaration order.
+    /// Piece-square weights are named by piece type and `SquareWeights` field, as in `knight_centre_mg`
+    pub const NAMES: [&'static str; 40] = [
+        "king",
+        "queen",
+        "rook",
+        "bishop",
+        "knight",
+        "pawn",
+        "isolated_pawn",
+        "doubled_pawn",
+        "backward_pawn",
+        "connected_pawn",
+        "passed_pawn",
+        "passed_pawn_advance",
+        "king_shield",
+        "king_open_file",
+        "king_zone_attack",
+        "movement",
+        "king_centre_mg",
+        "king_centre_eg",
+        "king_advance_mg",
+        "king_advance_eg",
+        "queen_centre_mg",
+        "queen_centre_eg",
+        "queen_advance_mg",
+        "queen_advance_eg",
+        "rook_centre_mg",
+        "rook_centre_eg",
+        "rook_advance_mg",
+        "rook_advance_eg",
+        "bishop_centre_mg",
+        "bishop_centre_eg",
+        "bishop_advance_mg",
+        "bishop_advance_eg",
+        "knight_centre_mg",
+        "knight_centre_eg",
+        "knight_advance_mg",
+        "knight_advance_eg",
+        "pawn_centre_mg",
+        "pawn_centre_eg",
+        "pawn_advance_mg",
+        "pawn_advance_eg",
+    ];
+
+    /// Material weight of a piece type
+    pub fn piece_value(&self, piece_type: PieceType) -> i32 {
+        match piece_type {
+            PieceType::King => self.king,
+            PieceType::Queen => self.queen,
+            PieceType::Rook => self.rook,
+            PieceType::Bishop => self.bishop,
+            PieceType::Knight => self.knight,
+            PieceType::Pawn => self.pawn,
+        }
+    }
+
+    /// Piece-square weights of a piece type
+    pub fn square_weights(&self, piece_type: PieceType) -> &SquareWeights {
+        match piece_type {
+            PieceType::King => &self.king_square,
+            PieceType::Queen => &self.queen_square,
+            PieceType::Rook => &self.rook_square,
+            PieceType::Bishop => &self.bishop_square,
+            PieceType::Knight => &self.knight_square,
+            PieceType::Pawn => &self.pawn_square,
+        }
+    }
+
+    /// Weight by its name, see `NAMES`
+    pub fn get(&self, name: &str) -> Option<i32> {
+        match name {
+            "king" => Some(self.king),
+            "queen" => Some(self.queen),
+            "rook" => Some(self.rook),
+            "bishop" => Some(self.bishop),
+            "knight" => Some(self.knight),
+            "pawn" => Some(self.pawn),
+            "isolated_pawn" => Some(self.isolated_pawn),
+            "doubled_pawn" => Some(self.doubled_pawn),
+            "backward_pawn" => Some(self.backward_pawn),
+            "connected_pawn" => Some(self.connected_pawn),
+            "passed_pawn" => Some(self.passed_pawn),
+            "passed_pawn_advance" => Some(self.passed_pawn_advance),
+            "king_shield" => Some(self.king_shield),
+            "king_open_file" => Some(self.king_open_file),
+            "king_zone_attack" => Some(self.king_zone_attack),
+            "movement" => Some(self.movement),
+            _ => {
+                let (piece, square_weight) = name.split_once('_')?;
+                let square_weights = match piece {
+                    "king" => &self.king_square,
+                    "queen" => &self.queen_square,
+                    "rook" => &self.rook_square,
+                    "bishop" => &self.bishop_square,
+                    "knight" => &self.knight_square,
+                    "pawn" => &self.pawn_square,
+                    _ => return None,
+                };
+                square_weights.get(square_weight)
+            }
+        }
+    }
+
+    /// Mutable access to a weight by its name, see `NAMES`
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut i32> {
+        match name {
+            "king" => Some(&mut self.king),
+            "queen" => Some(&mut self.queen),
+            "rook" => Some(&mut self.rook),
+            "bishop" => Some(&mut self.bishop),
+            "knight" => Some(&mut self.knight),
+            "pawn" => Some(&mut self.pawn),
+            "isolated_pawn" => Some(&mut self.isolated_pawn),
+            "doubled_pawn" => Some(&mut self.doubled_pawn),
+            "backward_pawn" => Some(&mut self.backward_pawn),
+            "connected_pawn" => Some(&mut self.connected_pawn),
+            "passed_pawn" => Some(&mut self.passed_pawn),
+            "passed_pawn_advance" => Some(&mut self.passed_pawn_advance),
+            "king_shield" => Some(&mut self.king_shield),
+            "king_open_file" => Some(&mut self.king_open_file),
+            "king_zone_attack" => Some(&mut self.king_zone_attack),
+            "movement" => Some(&mut self.movement),
+            _ => {
+                let (piece, square_weight) = name.split_once('_')?;
+                let square_weights = match piece {
+                    "king" => &mut self.king_square,
+                    "queen" => &mut self.queen_square,
+                    "rook" => &mut self.rook_square,
+                    "bishop" => &mut self.bishop_square,
+                    "knight" => &mut self.knight_square,
+                    "pawn" => &mut self.pawn_square,
+                    _ => return None,
+                };
+                square_weights.get_mut(square_weight)
+            }
+        }
+    }
+}
+
+/// Text format of the weights, a `<name> <value>` line per weight in `NAMES` order
+impl Display for Weights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for name in Self::NAMES {
+            writeln!(f, "{} {}", name, self.get(name).unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the text format of `Display`. Weights missing from the input keep their default,
+/// empty lines and lines starting with `#` are skipped
+impl FromStr for Weights {
+    type Err = WeightsError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut weights = Self::default();
+        for line in input.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let name = fields.next().unwrap_or_default();
+            let weight = weights
+                .get_mut(name)
+                .ok_or_else(|| WeightsError::UnknownWeight(name.to_string()))?;
+            *weight = match (fields.next(), fields.next()) {
+                (Some(value), None) => value
+                    .parse()
+                    .map_err(|_| WeightsError::InvalidValue(line.to_string()))?,
+                _ => return Err(WeightsError::InvalidValue(line.to_string())),
+            };
+        }
+        Ok(weights)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WeightsError {
+    UnknownWeight(String),
+    /// Line without exactly one integer after the name
+    InvalidValue(String),
+}
+
+impl Display for WeightsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownWeight(name) => write!(f, "unknown weight '{}'", name),
+            Self::InvalidValue(line) => write!(f, "invalid weight line '{}'", line),
+        }
+    }
+}
+
+impl Error for WeightsError {}
+
 /// Metadata stuct for search
 #[derive(Debug, Default)]
-pub struct SearchMeta {
-    ///identifier
-    id: u16,
+pub struct SearchMeta<E = ClassicalEvaluator> {
     current_tree: Vec<Ply>,
     nodes_visited: u64,
-    /// Index: WeightMap
-    weights: Weights,
+    evaluator: E,
     // PV
-    follow_pv: bool,
+    /// Triangular PV table, line at index `n` starts at tree height `n`
+    pv_lines: Vec<Vec<Ply>>,
+    /// Color to move at the root of the search
+    side_to_move: PieceColor,
+    config: SearchConfig,
+    /// Depth of the running iteration, bounds check extensions
+    root_depth: i8,
+    // Quiet ply ordering
+    killers: Killers,
+    history: History,
+    // Limits
+    stop: StopFlag,
+    node_limit: Option<u64>,
+    /// No further iteration is started after this point
+    soft_deadline: Option<Instant>,
+    /// The running iteration is aborted after this point
+    hard_deadline: Option<Instant>,
+    next_time_check: u64,
+    /// A limit has been reached, results of the running iteration are invalid
+    aborted: bool,
+    /// Limits are only enforced once the first iteration completed, so a ply is always found
+    abortable: bool,
+}
+
+/// Outcome of a completed iterative deepening iteration
+#[derive(Debug, Clone, PartialEq)]
+pub struct IterationReport {
+    pub depth: i8,
+    pub score: i32,
+    pub pv: Vec<Ply>,
+    /// Nodes visited since the start of the search
+    pub nodes: u64,
+    pub elapsed: Duration,
 }
-impl SearchMeta {
-    fn new() -> Self {
+impl<E: Evaluator> SearchMeta<E> {
+    fn with_limits(evaluator: E, side_to_move: PieceColor, limits: &SearchLimits) -> Self {
+        let (soft_deadline, hard_deadline) = limits.deadlines(Instant::now());
         Self {
-            id: rng().random::<u16>(),
-            ..Default::default()
+            current_tree: vec![],
+            nodes_visited: 0,
+            evaluator,
+            pv_lines: vec![],
+            side_to_move,
+            config: limits.config,
+            root_depth: 0,
+            killers: Killers::default(),
+            history: History::default(),
+            stop: limits.stop.clone(),
+            node_limit: limits.nodes,
+            soft_deadline,
+            hard_deadline,
+            next_time_check: 0,
+            aborted: false,
+            abortable: false,
         }
     }
 
-    fn with_weights(weights: Weights) -> Self {
-        Self {
-            weights,
-            ..Self::new()
+    /// Checks whether the search has to be aborted, the clock is only polled every `TIME_CHECK_INTERVAL` nodes
+    fn should_abort(&mut self) -> bool {
+        if self.aborted || !self.abortable {
+            return self.aborted;
+        }
+
+        if self.stop.is_stopped()
+            || self
+                .node_limit
+                .is_some_and(|limit| self.nodes_visited >= limit)
+        {
+            self.aborted = true;
+        } else if self.nodes_visited >= self.next_time_check {
+            self.next_time_check = self.nodes_visited + TIME_CHECK_INTERVAL;
+            self.aborted = self
+                .hard_deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
         }
+        self.aborted
     }
 
     fn last_ply_by(&self) -> PieceColor {
         self.current_tree
             .last()
-            .unwrap_or(&Ply {
-                moving_piece: BLACK_PAWN, // Default to Black, since White moves first
-                ..Default::default()
-            })
-            .moving_piece
-            .1
+            .map(|ply| ply.moving_piece.1)
+            .unwrap_or(self.side_to_move.next())
     }
 }
 
-impl Bitboards {
-    pub fn evaluate(&mut self, meta: &SearchMeta) -> i32 {
-        // if self.check_cache {
-        //     if let Some(eval) = self
-        //         .evaluation_table
-        //         .lock()
-        //         .unwrap()
-        //         .get(&self.zobrist_hash)
-        //     {
-        //         return *eval;
-        //     }
-        // }
-
-        // TODO: reweight pawn startegic positions
-        // TODO: Add strategic weight of pawns
-
-        // We need to:
-        // - count all pieces
-        // - count pawns per column per color for doubled and isolated counts
-        // - count legal moves, and count blocked pawns
-
-        // Material score
-        let material_score: i32 = self
-            .key_value_pieces_iter()
-            .map(|(piece, _)| match piece {
-                Piece(PieceType::King, color) => color.score_sign() * meta.weights.king,
-                Piece(PieceType::Queen, color) => color.score_sign() * meta.weights.queen,
-                Piece(PieceType::Rook, color) => color.score_sign() * meta.weights.rook,
-                Piece(PieceType::Bishop, color) => color.score_sign() * meta.weights.bishop,
-                Piece(PieceType::Knight, color) => color.score_sign() * meta.weights.knight,
-                Piece(PieceType::Pawn, color) => color.score_sign() * meta.weights.pawn,
-            })
-            .sum();
-
-        // Isolate pawn count
-        let window: u16 = 0b010;
-        let mask: u16 = 0b111;
-        let mut isolated_pawns_count = 0;
-
-        for color in PieceColor::iter() {
-            let pawns =
-                self.boards[bitboard_idx(Piece(PieceType::Pawn, color))].as_column_representation();
-
-            if pawns.trailing_ones() == 1 {
-                isolated_pawns_count += color.score_sign()
-            }
+/// Plys neither capturing nor promoting, ordered by the killer and history heuristics
+fn is_quiet(ply: &Ply) -> bool {
+    ply.capturing.is_none() && ply.promoting.is_none()
+}
 
-            if pawns.leading_ones() == 1 {
-                isolated_pawns_count += color.score_sign()
-            }
+impl Bitboards {
+    /// Static evaluation by the evaluator of `meta`, from the view of the side to move
+    pub fn evaluate<E: Evaluator>(&self, meta: &SearchMeta<E>) -> i32 {
+        meta.evaluator.evaluate(self) * meta.last_ply_by().next().score_sign()
+    }
 
-            for _ in 0..=13 {
-                let masked_pawns = pawns & mask;
-                if (masked_pawns | window).count_ones() == 1 {
-                    isolated_pawns_count += color.score_sign()
-                }
-            }
+    fn quiescence_search<E: Evaluator>(
+        &mut self,
+        meta: &mut SearchMeta<E>,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if meta.should_abort() {
+            return 0;
         }
 
-        let pawn_score = meta.weights.isolated_pawn * isolated_pawns_count;
-
-        // Move score
-        let move_score = self
-            .all_legal_plys_by_color::<Vec<Ply>>(PieceColor::White)
-            .len() as i32
-            - self
-                .all_legal_plys_by_color::<Vec<Ply>>(PieceColor::Black)
-                .len() as i32;
-
-        (material_score + pawn_score + (meta.weights.movement * move_score))
-            * meta.last_ply_by().next().score_sign()
-
-        // self.evaluation_table
-        //     .lock()
-        //     .unwrap()
-        //     .insert(*self.zobrist_hash, score);
-    }
-
-    fn quiescence_search(&mut self, meta: &mut SearchMeta, mut alpha: i32, beta: i32) -> i32 {
-        // Check cached results
-        if self.check_quiescence_table {
-            if let Some(result) = self.quiescence_table.lock().unwrap().get(&(
-                *self.zobrist_hash,
-                meta.id,
-                meta.last_ply_by() as u8,
-            )) {
-                return *result;
-            } else {
-                self.check_quiescence_table = false;
+        // Any stored search of this position is at least as deep as quiescence
+        let height = meta.current_tree.len();
+        let stored = self.transposition_table.probe(self.zobrist_hash);
+        if let Some(entry) = stored {
+            let score = score_from_table(entry.score, height);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => (),
             }
         }
+        let original_alpha = alpha;
 
         let eval = self.evaluate(meta);
         let mut best_score = eval;
 
         //beta cutoff
         if eval >= beta {
+            self.store_transposition(meta, 0, Bound::Lower, beta, None);
             return beta;
         }
 
@@ -182,16 +499,30 @@ impl Bitboards {
             alpha = eval;
         }
 
-        for ply in self.all_legal_capturing_plys_by_color::<Vec<Ply>>(meta.last_ply_by().next()) {
+        // Captures losing material in the exchange are pruned, the rest tried by exchange gain
+        let mut captures =
+            self.all_legal_capturing_plys_by_color::<MoveList>(meta.last_ply_by().next());
+        captures.retain_mut(|ply| {
+            ply.order_score = self.static_exchange(ply, &meta.evaluator);
+            ply.order_score >= 0
+        });
+        captures.sort_unstable_by(|a, b| b.cmp(a));
+
+        for ply in captures {
             meta.nodes_visited += 1;
             self.make_ply(&ply);
+            meta.evaluator.make_ply(self, &ply);
             meta.current_tree.push(ply);
 
             let score = self
                 .quiescence_search(meta, beta.saturating_neg(), alpha.saturating_neg())
                 .saturating_neg();
             let last_ply = meta.current_tree.pop().unwrap_or_default();
-            self.unmake_ply(&last_ply, meta.current_tree.last());
+            self.unmake_ply(&last_ply);
+            meta.evaluator.unmake_ply(self, &last_ply);
+            if meta.aborted {
+                return 0;
+            }
 
             if score > best_score {
                 best_score = score;
@@ -204,21 +535,40 @@ impl Bitboards {
             }
         }
 
-        self.quiescence_table.lock().unwrap().insert(
-            (*self.zobrist_hash, meta.id, meta.last_ply_by() as u8),
-            best_score,
-        );
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score <= original_alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        self.store_transposition(meta, 0, bound, best_score, None);
         best_score
     }
 
-    fn alpha_beta(
+    fn alpha_beta<E: Evaluator>(
         &mut self,
-        meta: &mut SearchMeta,
+        meta: &mut SearchMeta<E>,
         mut alpha: i32,
         beta: i32,
-        depth: i8,
+        mut depth: i8,
     ) -> (i32, Option<Ply>) {
-        if depth == 0 {
+        if meta.should_abort() {
+            return (0, None);
+        }
+
+        let height = meta.current_tree.len();
+        if height == 0 {
+            meta.root_depth = depth;
+        }
+        let side_to_move = meta.last_ply_by().next();
+        let in_check = self.in_check(side_to_move);
+        // Extensions stop at twice the iteration depth, so checking sequences stay bounded
+        if in_check && meta.config.check_extensions && height < 2 * meta.root_depth as usize {
+            depth += 1;
+        }
+
+        if depth <= 0 {
             return (
                 self.quiescence_search(meta, alpha, beta),
                 meta.current_tree.last().cloned(),
@@ -227,81 +577,339 @@ impl Bitboards {
 
         let mut best_move = (i32::MIN, None);
 
-        let mut priority_queue =
-            self.all_legal_plys_by_color::<BinaryHeap<Ply>>(meta.last_ply_by().next());
-
-        // PV following
-        if meta.follow_pv {
-            meta.follow_pv = false;
-            if let Some(&pv) = self
-                .pv_table
-                .lock()
-                .unwrap()
-                .get(&(*self.zobrist_hash, meta.id))
-            {
-                meta.follow_pv = true;
-                priority_queue.push(pv);
+        if meta.pv_lines.len() < height + 2 {
+            meta.pv_lines.resize(height + 2, vec![]);
+        }
+        meta.pv_lines[height].clear();
+
+        // Transposition cutoffs, exact scores only outside of the PV to keep it intact
+        let original_alpha = alpha;
+        let pv_node = beta.saturating_sub(alpha) > 1;
+        let stored = self.transposition_table.probe(self.zobrist_hash);
+        if let Some(entry) = stored
+            && height > 0
+            && entry.depth >= depth
+        {
+            let score = score_from_table(entry.score, height);
+            match entry.bound {
+                Bound::Exact if !pv_node || score >= beta || score <= alpha => {
+                    return (score, None);
+                }
+                Bound::Lower if score >= beta => return (score, None),
+                Bound::Upper if score <= alpha => return (score, None),
+                _ => (),
             }
         }
 
-        for this_move in priority_queue {
+        // A position failing high even after passing is assumed to fail high after any ply
+        if meta.config.null_move_pruning
+            && !pv_node
+            && !in_check
+            && height > 0
+            && depth >= NULL_MOVE_MIN_DEPTH
+            && beta.abs() < MATE_SCORE - MAX_MATE_DISTANCE
+            && !meta.current_tree.last().is_some_and(Ply::is_null)
+            && self.has_non_pawn_material(side_to_move)
+        {
+            meta.nodes_visited += 1;
+            self.make_null_ply();
+            meta.evaluator.make_null_ply(self);
+            meta.current_tree.push(Ply::null(side_to_move));
+            meta.pv_lines[height + 1].clear();
+            let score = self.child_score(meta, beta - 1, beta, depth - 1 - NULL_MOVE_REDUCTION);
+            meta.current_tree.pop();
+            self.unmake_null_ply();
+            meta.evaluator.unmake_null_ply(self);
+            if meta.aborted {
+                return (0, None);
+            }
+            // Mates found after passing are not proven
+            if score >= beta {
+                return (beta, None);
+            }
+        }
+
+        // The best ply of an earlier search is tried first, captures by exchange gain and quiet plys by earlier cutoffs
+        let mut picker = MovePicker::new(
+            side_to_move,
+            height,
+            stored.and_then(|entry| entry.best_ply),
+        );
+
+        let mut index = 0;
+        while let Some(this_move) = picker.next(self, meta) {
             meta.nodes_visited += 1;
             self.make_ply(&this_move);
+            meta.evaluator.make_ply(self, &this_move);
             meta.current_tree.push(this_move);
-            let score = self
-                .alpha_beta(
-                    meta,
-                    beta.saturating_neg(),
-                    alpha.saturating_neg(),
-                    depth - 1,
-                )
-                .0
-                .saturating_neg();
+            meta.pv_lines[height + 1].clear();
+            // thricefold repetition is a draw
+            let score = if self.repetition_count() >= 3 {
+                0
+            } else {
+                let reduction = if meta.config.late_move_reductions
+                    && depth >= LMR_MIN_DEPTH
+                    && index >= LMR_FULL_DEPTH_PLYS
+                    && !in_check
+                    && is_quiet(&this_move)
+                    && !self.in_check(side_to_move.next())
+                {
+                    1
+                } else {
+                    0
+                };
+                let zero_window = meta.config.principal_variation_search && index > 0;
+                let window_beta = if zero_window {
+                    alpha.saturating_add(1)
+                } else {
+                    beta
+                };
+
+                let mut score = self.child_score(meta, alpha, window_beta, depth - 1 - reduction);
+                if reduction > 0 && score > alpha {
+                    score = self.child_score(meta, alpha, window_beta, depth - 1);
+                }
+                if zero_window && score > alpha && score < beta {
+                    score = self.child_score(meta, alpha, beta, depth - 1);
+                }
+                score
+            };
             let last_ply = meta.current_tree.pop().unwrap_or_default();
-            self.unmake_ply(&last_ply, meta.current_tree.last());
+            self.unmake_ply(&last_ply);
+            meta.evaluator.unmake_ply(self, &last_ply);
+            if meta.aborted {
+                return (0, None);
+            }
 
             if score > best_move.0 {
                 best_move = (score, Some(this_move));
                 if score > alpha {
                     alpha = score;
                 }
+                let mut line = vec![this_move];
+                line.extend_from_slice(&meta.pv_lines[height + 1]);
+                meta.pv_lines[height] = line;
             }
             if score >= beta {
+                if is_quiet(&this_move) {
+                    meta.killers.store(height, &this_move);
+                    meta.history.reward(&this_move, depth);
+                }
                 break;
             }
+            index += 1;
         }
-        if let Some(mut pv) = best_move.1 {
-            pv.pv_move = true;
-            self.pv_table
-                .lock()
-                .unwrap()
-                .insert((*self.zobrist_hash, meta.id), pv);
+        // No legal plys: checkmate, scored by distance to root to prefer faster mates, or stalemate
+        if best_move.1.is_none() {
+            let score = if in_check {
+                -(MATE_SCORE - height as i32)
+            } else {
+                0
+            };
+            self.store_transposition(meta, depth, Bound::Exact, score, None);
+            return (score, None);
         }
 
+        let bound = if best_move.0 >= beta {
+            Bound::Lower
+        } else if best_move.0 <= original_alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        self.store_transposition(meta, depth, bound, best_move.0, best_move.1);
+
         best_move
     }
 
-    /// Searches the next best ply at a given depth + quienscence search;
+    /// Score of the position after a ply within the window of the parent node, from its view
+    fn child_score<E: Evaluator>(
+        &mut self,
+        meta: &mut SearchMeta<E>,
+        alpha: i32,
+        beta: i32,
+        depth: i8,
+    ) -> i32 {
+        self.alpha_beta(meta, beta.saturating_neg(), alpha.saturating_neg(), depth)
+            .0
+            .saturating_neg()
+    }
+
+    /// Whether `color` has pieces besides its king and pawns, without them passing may be its best option
+    fn has_non_pawn_material(&self, color: PieceColor) -> bool {
+        [
+            PieceType::Queen,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Knight,
+        ]
+        .into_iter()
+        .any(|piece_type| *self.boards[bitboard_idx(Piece(piece_type, color))] != 0)
+    }
+
+    fn store_transposition<E>(
+        &self,
+        meta: &SearchMeta<E>,
+        depth: i8,
+        bound: Bound,
+        score: i32,
+        best_ply: Option<Ply>,
+    ) {
+        self.transposition_table.store(TranspositionEntry {
+            hash: self.zobrist_hash,
+            depth,
+            bound,
+            best_ply: best_ply.as_ref().map(PlyKey::from),
+            score: score_to_table(score, meta.current_tree.len()),
+            ..Default::default()
+        });
+    }
+
+    /// Searches the next best ply within `limits` + quienscence search;
     /// Returns the (score, best_ply, visited_nodes_count)
     pub fn search_next_ply(
         &mut self,
-        last_ply: Option<Ply>,
-        depth: i8,
+        side_to_move: PieceColor,
+        limits: &SearchLimits,
         weights: Weights,
     ) -> (i32, Option<Ply>, u64) {
-        let mut meta = SearchMeta::with_weights(weights);
-        if let Some(last_ply) = last_ply {
-            meta.current_tree.push(last_ply);
+        let (score, pv, nodes) = self.search_pv(side_to_move, limits, weights);
+        (score, pv.first().copied(), nodes)
+    }
+
+    /// Like `search_next_ply`, but returns the whole principal variation
+    /// Returns the (score, principal_variation, visited_nodes_count)
+    pub fn search_pv(
+        &mut self,
+        side_to_move: PieceColor,
+        limits: &SearchLimits,
+        weights: Weights,
+    ) -> (i32, Vec<Ply>, u64) {
+        self.search_with_limits(
+            side_to_move,
+            limits,
+            ClassicalEvaluator::from(weights),
+            &mut |_| {},
+        )
+    }
+
+    /// Deepens the search until one of `limits` is reached, `report` is called after every completed iteration.
+    /// The search runs on a thread of `SEARCH_STACK_SIZE`, whatever the stack of the caller.
+    /// With more than one thread, helpers search clones of the position alongside and share the
+    /// transposition table (Lazy SMP), the main search thread decides when the search ends.
+    /// Returns the (score, principal_variation, visited_nodes_count) of the last completed iteration,
+    /// the node count includes those of the helpers
+    pub fn search_with_limits<E: Evaluator>(
+        &mut self,
+        side_to_move: PieceColor,
+        limits: &SearchLimits,
+        mut evaluator: E,
+        report: &mut (dyn FnMut(&IterationReport) + Send),
+    ) -> (i32, Vec<Ply>, u64) {
+        evaluator.reset(self);
+        self.transposition_table.new_search();
+        let depth = limits.depth.unwrap_or(MAX_DEPTH);
+
+        // Helpers end with the main search, which itself watches the limits' stop flag
+        let helpers_stop = StopFlag::default();
+        let helper_limits = SearchLimits {
+            stop: helpers_stop.clone(),
+            ..limits.clone()
+        };
+        std::thread::scope(|scope| {
+            let helpers: Vec<_> = (1..limits.threads.max(1))
+                .map(|thread| {
+                    let mut boards = self.clone();
+                    let evaluator = evaluator.clone();
+                    let helper_limits = &helper_limits;
+                    std::thread::Builder::new()
+                        .stack_size(SEARCH_STACK_SIZE)
+                        .spawn_scoped(scope, move || {
+                            let mut meta =
+                                SearchMeta::with_limits(evaluator, side_to_move, helper_limits);
+                            boards.helper_deepening(&mut meta, thread, depth)
+                        })
+                        .expect("failed to spawn search helper")
+                })
+                .collect();
+
+            let main = std::thread::Builder::new()
+                .stack_size(SEARCH_STACK_SIZE)
+                .spawn_scoped(scope, || {
+                    let mut meta = SearchMeta::with_limits(evaluator, side_to_move, limits);
+                    let (score, pv) = self.iterative_deepening(&mut meta, depth, report);
+                    (score, pv, meta.nodes_visited)
+                })
+                .expect("failed to spawn search");
+            let result = main.join();
+            helpers_stop.stop();
+            let helper_nodes: u64 = helpers
+                .into_iter()
+                .map(|helper| helper.join().expect("search helper panicked"))
+                .sum();
+            let (score, pv, nodes) =
+                result.unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            (score, pv, nodes + helper_nodes)
+        })
+    }
+
+    /// Deepening of a Lazy SMP helper, which only fills the shared transposition table.
+    /// Every other helper starts one ply deeper, so threads spread over different depths.
+    /// Returns the visited nodes count
+    fn helper_deepening<E: Evaluator>(
+        &mut self,
+        meta: &mut SearchMeta<E>,
+        thread: usize,
+        depth: i8,
+    ) -> u64 {
+        // Helpers have no result to keep, they may stop at any point
+        meta.abortable = true;
+        let first_depth = 1 + (thread % 2) as i8;
+        for i in first_depth..=depth {
+            self.alpha_beta(meta, i32::MIN, i32::MAX, i);
+            if meta.aborted {
+                break;
+            }
         }
-        let result = self.iterative_deepening(&mut meta, depth);
-        (result.0, result.1, meta.nodes_visited)
+        meta.nodes_visited
     }
 
-    pub fn iterative_deepening(&mut self, meta: &mut SearchMeta, depth: i8) -> (i32, Option<Ply>) {
-        let mut result = (0, None);
+    /// Searches with increasing depth until `depth`, a limit of `meta` or a forced mate is reached.
+    /// Returns the score and principal variation of the last completed iteration
+    pub fn iterative_deepening<E: Evaluator>(
+        &mut self,
+        meta: &mut SearchMeta<E>,
+        depth: i8,
+        report: &mut dyn FnMut(&IterationReport),
+    ) -> (i32, Vec<Ply>) {
+        let start = Instant::now();
+        let mut result = (0, vec![]);
         for i in 1..=depth {
-            meta.follow_pv = true;
-            result = self.alpha_beta(meta, i32::MIN, i32::MAX, i);
+            let (score, _) = self.alpha_beta(meta, i32::MIN, i32::MAX, i);
+            if meta.aborted {
+                break;
+            }
+            meta.abortable = true;
+
+            let pv = meta.pv_lines.first().cloned().unwrap_or_default();
+            report(&IterationReport {
+                depth: i,
+                score,
+                pv: pv.clone(),
+                nodes: meta.nodes_visited,
+                elapsed: start.elapsed(),
+            });
+
+            // Deeper iterations can't find a shorter mate or a longer defence
+            let mate_found = mate_distance(score).is_some_and(|plys| plys.abs() <= i as i32);
+            let out_of_time = meta
+                .soft_deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+            let no_plys = pv.is_empty();
+            result = (score, pv);
+            if no_plys || mate_found || out_of_time {
+                break;
+            }
         }
 
         result
@@ -310,34 +918,34 @@ impl Bitboards {
 
 #[cfg(test)]
 mod tests {
-    use std::i32::{MAX, MIN};
-
     use super::*;
-    use crate::chess_engine::{game::Game, pieces::WHITE_ROOK};
+    use crate::chess_engine::{
+        bitboard::move_gen::move_list::MAX_PLYS, game::Game, pieces::WHITE_ROOK,
+    };
 
     #[test]
     fn evaluate_default() {
         let game = Game::default();
-        let mut boards = game.boards;
-        let score = boards.evaluate(&SearchMeta::default());
+        let boards = game.boards;
+        let score = boards.evaluate(&SearchMeta::<ClassicalEvaluator>::default());
         assert_eq!(score, 0);
     }
 
     #[test]
     fn evaluate_material_score() {
-        let mut boards = Bitboards::new_from_str(
+        let boards = Bitboards::new_from_str(
             r#"
             ppP
             PPP
             "#,
         );
-        let score = boards.evaluate(&SearchMeta::default());
+        let score = boards.evaluate(&SearchMeta::<ClassicalEvaluator>::default());
         assert!(score.is_negative());
     }
 
     #[test]
     fn evaluate_movement_score() {
-        let mut boards = Bitboards::new_from_str(
+        let boards = Bitboards::new_from_str(
             r#"
             00000
             00000
@@ -346,13 +954,13 @@ mod tests {
             0000Q
             "#,
         );
-        let score = boards.evaluate(&SearchMeta::default());
+        let score = boards.evaluate(&SearchMeta::<ClassicalEvaluator>::default());
         assert!(score.is_positive());
     }
 
     #[test]
     fn evaluate_isolated_pawns_score() {
-        let mut boards = Bitboards::new_from_str(
+        let boards = Bitboards::new_from_str(
             r#"
             PPPPPPPP
             00000000
@@ -362,7 +970,10 @@ mod tests {
             p000p00p
             "#,
         );
-        let score = boards.evaluate(&SearchMeta::default());
+        // white pawns stand further advanced, only pawn structure is weighed here
+        let mut meta: SearchMeta = SearchMeta::default();
+        meta.evaluator.weights.pawn_square = SquareWeights::default();
+        let score = boards.evaluate(&meta);
         assert!(score.is_negative());
     }
 
@@ -376,9 +987,10 @@ mod tests {
             r000
             "#,
         );
-        let mut meta = SearchMeta::default();
-        let _score = boards.quiescence_search(&mut meta, MIN, MAX);
-        assert_eq!(meta.nodes_visited, 7);
+        let mut meta: SearchMeta = SearchMeta::default();
+        let _score = boards.quiescence_search(&mut meta, i32::MIN, i32::MAX);
+        // captures losing the exchange are pruned
+        assert_eq!(meta.nodes_visited, 5);
     }
 
     #[test]
@@ -391,9 +1003,9 @@ mod tests {
             0r0
             "#,
         );
-        let mut meta = SearchMeta::default();
-        let _score = boards.alpha_beta(&mut meta, MIN, MAX, 1);
-        assert_eq!(meta.nodes_visited, 11);
+        let mut meta: SearchMeta = SearchMeta::default();
+        let _score = boards.alpha_beta(&mut meta, i32::MIN, i32::MAX, 1);
+        assert_eq!(meta.nodes_visited, 15);
     }
 
     #[test]
@@ -406,8 +1018,8 @@ mod tests {
             0r0
             "#,
         );
-        let mut meta = SearchMeta::default();
-        let result = boards.alpha_beta(&mut meta, MIN, MAX, 1);
+        let mut meta: SearchMeta = SearchMeta::default();
+        let result = boards.alpha_beta(&mut meta, i32::MIN, i32::MAX, 1);
         assert!(result.1.is_some());
         assert_eq!(result.1.unwrap().moving_piece, WHITE_ROOK)
     }
@@ -421,8 +1033,14 @@ mod tests {
             0r0
             "#,
         );
-        let result = boards.search_next_ply(None, 3, Weights::default());
+        let result = boards.search_next_ply(
+            PieceColor::White,
+            &SearchLimits::depth(3),
+            Weights::default(),
+        );
         assert!(result.1.is_none());
+        assert_eq!(result.0, -MATE_SCORE);
+        assert_eq!(mate_distance(result.0), Some(0));
     }
 
     #[test]
@@ -434,25 +1052,408 @@ mod tests {
             0R0
             "#,
         );
-        let result = boards.search_next_ply(None, 3, Weights::default());
+        let result = boards.search_next_ply(
+            PieceColor::White,
+            &SearchLimits::depth(3),
+            Weights::default(),
+        );
         assert!(result.1.is_some());
-        let ply = result.1;
-        boards.make_ply(&ply.unwrap());
-        let result = boards.search_next_ply(ply, 3, Weights::default());
+        boards.make_ply(&result.1.unwrap());
+        let result = boards.search_next_ply(
+            PieceColor::Black,
+            &SearchLimits::depth(3),
+            Weights::default(),
+        );
         assert!(result.1.is_none());
     }
 
+    #[test]
+    fn stalemate_search() {
+        let (mut boards, _) = Bitboards::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = boards.search_next_ply(
+            PieceColor::Black,
+            &SearchLimits::depth(3),
+            Weights::default(),
+        );
+        assert!(result.1.is_none());
+        assert_eq!(result.0, 0);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn search_with_more_plys_than_move_list_capacity() {
+        // eight queens on a 16x16 board give white more plys than a move list holds inline
+        let (mut boards, _) = Bitboards::from_fen(
+            "k15/16/Q1Q1Q1Q1Q1Q1Q1Q1/16/16/16/16/16/16/16/16/16/16/16/16/K15 w - - 0 1",
+        )
+        .unwrap();
+        assert!(
+            boards
+                .all_legal_plys_by_color::<Vec<Ply>>(PieceColor::White)
+                .len()
+                > MAX_PLYS
+        );
+
+        let result = boards.search_next_ply(
+            PieceColor::White,
+            &SearchLimits::depth(2),
+            Weights::default(),
+        );
+        assert!(result.1.is_some());
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn mate_in_one_search() {
+        let (mut boards, _) = Bitboards::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = boards.search_next_ply(
+            PieceColor::White,
+            &SearchLimits::depth(2),
+            Weights::default(),
+        );
+        assert_eq!(mate_distance(result.0), Some(1));
+        assert_eq!(result.1.unwrap().to, boards.parse_square("a8").unwrap());
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn mate_instead_of_stalemate_search() {
+        // Qc7 would stalemate, Qc8 mates
+        let (mut boards, _) = Bitboards::from_fen("k7/8/1K6/8/8/8/8/2Q5 w - - 0 1").unwrap();
+        let result = boards.search_next_ply(
+            PieceColor::White,
+            &SearchLimits::depth(2),
+            Weights::default(),
+        );
+        assert_eq!(mate_distance(result.0), Some(1));
+        assert_eq!(result.1.unwrap().to, boards.parse_square("c8").unwrap());
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn mate_in_two_search() {
+        let (mut boards, _) = Bitboards::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        let result = boards.search_next_ply(
+            PieceColor::White,
+            &SearchLimits::depth(4),
+            Weights::default(),
+        );
+        assert_eq!(mate_distance(result.0), Some(3));
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn search_pv_leads_to_mate() {
+        let (mut boards, _) = Bitboards::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        let (score, pv, _) = boards.search_pv(
+            PieceColor::White,
+            &SearchLimits::depth(4),
+            Weights::default(),
+        );
+        assert_eq!(mate_distance(score), Some(3));
+        assert_eq!(pv.len(), 3);
+
+        for ply in pv.iter() {
+            boards.make_ply(ply);
+        }
+        assert!(boards.in_check(PieceColor::Black));
+        assert!(
+            boards
+                .all_legal_plys_by_color::<Vec<Ply>>(PieceColor::Black)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn weights_by_name() {
+        let mut weights = Weights::default();
+        for name in Weights::NAMES {
+            assert_eq!(weights.get(name), weights.get_mut(name).copied());
+            assert!(weights.get(name).is_some());
+        }
+        *weights.get_mut("queen").unwrap() = 200;
+        assert_eq!(weights.queen, 200);
+        assert_eq!(weights.get("queen"), Some(200));
+        assert_eq!(
+            weights.get("rook_advance_eg"),
+            Some(weights.rook_square.advance_eg)
+        );
+        assert!(weights.get_mut("unknown").is_none());
+        assert!(weights.get("unknown").is_none());
+    }
+
+    #[test]
+    fn weights_text_round_trip() {
+        let mut weights = Weights::default();
+        weights.knight_square.centre_eg = -3;
+        weights.movement = 7;
+        assert_eq!(weights.to_string().parse(), Ok(weights));
+
+        let weights: Weights = "# tuned\n\nqueen 200\n".parse().unwrap();
+        assert_eq!(weights.queen, 200);
+        assert_eq!(weights.rook, Weights::default().rook);
+
+        assert_eq!(
+            "contempt 10".parse::<Weights>(),
+            Err(WeightsError::UnknownWeight("contempt".to_string()))
+        );
+        assert_eq!(
+            "queen 1 2".parse::<Weights>(),
+            Err(WeightsError::InvalidValue("queen 1 2".to_string()))
+        );
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn longest_defence_search() {
+        // Black is mated next ply whatever it does
+        let (mut boards, _) = Bitboards::from_fen("7k/8/6K1/8/8/8/8/R7 b - - 0 1").unwrap();
+        let result = boards.search_next_ply(
+            PieceColor::Black,
+            &SearchLimits::depth(3),
+            Weights::default(),
+        );
+        assert!(result.1.is_some());
+        assert_eq!(mate_distance(result.0), Some(-2));
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn avoid_stalemate_when_winning_search() {
+        // Rb7 would stalemate, Rc1 forces mate with Rc8
+        let (mut boards, _) = Bitboards::from_fen("k7/8/1K6/8/8/8/8/1R6 w - - 0 1").unwrap();
+        let result = boards.search_next_ply(
+            PieceColor::White,
+            &SearchLimits::depth(4),
+            Weights::default(),
+        );
+        assert_eq!(mate_distance(result.0), Some(3));
+        assert_ne!(result.1.unwrap().to, boards.parse_square("b7").unwrap());
+    }
+
     #[test]
     #[cfg(not(miri))]
     fn iterative_deepening_pv_trim_nodes() {
         let mut boards = Game::default().boards;
 
-        let mut iterative_meta = SearchMeta::default();
-        let _iterative = boards.iterative_deepening(&mut iterative_meta, 3);
+        let mut iterative_meta: SearchMeta = SearchMeta::default();
+        let _iterative = boards.iterative_deepening(&mut iterative_meta, 6, &mut |_| {});
 
-        let mut exhaustive_meta = SearchMeta::default();
-        let _exhaustive = boards.alpha_beta(&mut exhaustive_meta, MIN, MAX, 3);
+        // both searches start without stored positions
+        boards.transposition_table.clear();
+        let mut exhaustive_meta: SearchMeta = SearchMeta::default();
+        let _exhaustive = boards.alpha_beta(&mut exhaustive_meta, i32::MIN, i32::MAX, 6);
 
         assert!(iterative_meta.nodes_visited < exhaustive_meta.nodes_visited);
     }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn search_reports_every_completed_depth() {
+        let mut boards = Game::default().boards;
+        let mut reports = vec![];
+        let result = boards.search_with_limits(
+            PieceColor::White,
+            &SearchLimits::depth(3),
+            ClassicalEvaluator::default(),
+            &mut |report| reports.push(report.clone()),
+        );
+        assert_eq!(
+            reports
+                .iter()
+                .map(|report| report.depth)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        let last = reports.last().unwrap();
+        assert_eq!(
+            (last.score, &last.pv, last.nodes),
+            (result.0, &result.1, result.2)
+        );
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn search_node_limit() {
+        let mut boards = Game::default().boards;
+        let limits = SearchLimits {
+            nodes: Some(2_000),
+            ..Default::default()
+        };
+        let (_, pv, nodes) = boards.search_with_limits(
+            PieceColor::White,
+            &limits,
+            ClassicalEvaluator::default(),
+            &mut |_| {},
+        );
+        assert!(!pv.is_empty());
+        assert!(nodes <= 2_000);
+    }
+
+    #[test]
+    fn search_stopped_keeps_first_iteration() {
+        let mut boards = Game::default().boards;
+        let limits = SearchLimits::depth(5);
+        limits.stop.stop();
+        let mut depths = vec![];
+        let (_, pv, _) = boards.search_with_limits(
+            PieceColor::White,
+            &limits,
+            ClassicalEvaluator::default(),
+            &mut |report| depths.push(report.depth),
+        );
+        assert_eq!(depths, vec![1]);
+        assert!(!pv.is_empty());
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn search_movetime() {
+        let mut boards = Game::default().boards;
+        let start = Instant::now();
+        let (_, pv, _) = boards.search_with_limits(
+            PieceColor::White,
+            &SearchLimits::movetime(Duration::from_millis(200)),
+            ClassicalEvaluator::default(),
+            &mut |_| {},
+        );
+        assert!(!pv.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn selective_search_visits_fewer_nodes() {
+        let search = |config| {
+            let mut boards = Game::default().boards;
+            boards.search_with_limits(
+                PieceColor::White,
+                &SearchLimits::depth(4).with_config(config),
+                ClassicalEvaluator::default(),
+                &mut |_| {},
+            )
+        };
+        let (_, plain_pv, plain_nodes) = search(SearchConfig::PLAIN);
+        let (_, pv, nodes) = search(SearchConfig::default());
+        assert!(!plain_pv.is_empty() && !pv.is_empty());
+        assert!(nodes < plain_nodes);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn each_technique_keeps_forced_mate() {
+        let configs = [
+            SearchConfig::PLAIN,
+            SearchConfig {
+                principal_variation_search: true,
+                ..SearchConfig::PLAIN
+            },
+            SearchConfig {
+                null_move_pruning: true,
+                ..SearchConfig::PLAIN
+            },
+            SearchConfig {
+                late_move_reductions: true,
+                ..SearchConfig::PLAIN
+            },
+            SearchConfig {
+                check_extensions: true,
+                ..SearchConfig::PLAIN
+            },
+        ];
+        for config in configs {
+            let (mut boards, _) = Bitboards::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+            let (score, pv, _) = boards.search_with_limits(
+                PieceColor::White,
+                &SearchLimits::depth(4).with_config(config),
+                ClassicalEvaluator::default(),
+                &mut |_| {},
+            );
+            assert_eq!(mate_distance(score), Some(3), "{:?}", config);
+            assert_eq!(pv.len(), 3, "{:?}", config);
+        }
+    }
+
+    #[test]
+    fn null_move_skipped_without_pieces() {
+        let (boards, _) = Bitboards::from_fen("4k3/4p3/8/8/8/8/4P3/3RK3 w - - 0 1").unwrap();
+        assert!(boards.has_non_pawn_material(PieceColor::White));
+        assert!(!boards.has_non_pawn_material(PieceColor::Black));
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn quiet_cutoffs_fill_killers_and_history() {
+        let mut boards = Game::default().boards;
+        let mut meta: SearchMeta = SearchMeta::default();
+        boards.iterative_deepening(&mut meta, 3, &mut |_| {});
+
+        let cutoffs: Vec<Ply> = boards
+            .all_legal_plys_by_color::<Vec<Ply>>(PieceColor::White)
+            .into_iter()
+            .chain(boards.all_legal_plys_by_color::<Vec<Ply>>(PieceColor::Black))
+            .filter(|ply| meta.history.score(ply) > 0)
+            .collect();
+        assert!(!cutoffs.is_empty());
+        assert!(cutoffs.iter().all(is_quiet));
+        assert!((0..3).any(|height| {
+            cutoffs
+                .iter()
+                .any(|ply| meta.killers.slot(height, ply).is_some())
+        }));
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn search_from_small_stack() {
+        // far too small for the move lists of a few plys deep search
+        let caller = std::thread::Builder::new().stack_size(256 << 10);
+        let mut boards = Game::default().boards;
+        let result = caller
+            .spawn(move || {
+                boards.search_next_ply(
+                    PieceColor::White,
+                    &SearchLimits::depth(4),
+                    Weights::default(),
+                )
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(result.1.is_some());
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn parallel_search_finds_mate() {
+        let (mut boards, _) = Bitboards::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        let mut depths = vec![];
+        let (score, pv, _) = boards.search_with_limits(
+            PieceColor::White,
+            &SearchLimits::depth(4).with_threads(4),
+            ClassicalEvaluator::default(),
+            &mut |report| depths.push(report.depth),
+        );
+        assert_eq!(mate_distance(score), Some(3));
+        assert_eq!(pv.len(), 3);
+        assert_eq!(depths.first(), Some(&1));
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn transposition_table_reused_between_searches() {
+        let mut boards = Game::default().boards;
+        let (first_score, _, first_nodes) = boards.search_pv(
+            PieceColor::White,
+            &SearchLimits::depth(3),
+            Weights::default(),
+        );
+        let (second_score, _, second_nodes) = boards.search_pv(
+            PieceColor::White,
+            &SearchLimits::depth(3),
+            Weights::default(),
+        );
+        assert_eq!(first_score, second_score);
+        assert!(second_nodes < first_nodes);
+    }
 }
@@ -0,0 +1,35 @@
+/// Selective search techniques, each can be disabled to measure its effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchConfig {
+    /// Principal variation search, plys after the first are searched with a zero window
+    /// and only re-searched with the full window if they raise alpha
+    pub principal_variation_search: bool,
+    /// Giving the opponent a free ply, a position still failing high is pruned.
+    /// Skipped in check and when the side to move only has pawns left, where passing may be best
+    pub null_move_pruning: bool,
+    /// Late quiet plys are searched with reduced depth, and again at full depth if they raise alpha
+    pub late_move_reductions: bool,
+    /// Positions in check are searched one ply deeper
+    pub check_extensions: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            principal_variation_search: true,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            check_extensions: true,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Plain alpha-beta search without any selectivity
+    pub const PLAIN: Self = Self {
+        principal_variation_search: false,
+        null_move_pruning: false,
+        late_move_reductions: false,
+        check_extensions: false,
+    };
+}
@@ -0,0 +1,289 @@
+//! Static evaluation of the positions the search reaches. The search is generic over the
+//! `Evaluator`, which is told about every ply made and unmade, passed turns of null move pruning
+//! included, so it can keep incremental state.
+//! `ClassicalEvaluator` is the default: material, piece-square tables, pawn structure, king safety
+//! and mobility, weighted by `Weights`.
+
+use crate::chess_engine::{
+    bitboard::{BitIndex, Bitboards, Ply},
+    pieces::{Piece, PieceColor, PieceType},
+};
+
+use super::{
+    Weights,
+    piece_square::{BoardArea, phase_weight, taper},
+};
+
+pub trait Evaluator: Clone + Send {
+    /// Score of the position from the view of white
+    fn evaluate(&self, boards: &Bitboards) -> i32;
+
+    /// Material value of a piece type, by which captures are ordered and pruned
+    fn piece_value(&self, piece_type: PieceType) -> i32;
+
+    /// Called with the root position before a search starts
+    fn reset(&mut self, _boards: &Bitboards) {}
+
+    /// Called after `ply` has been made on `boards`
+    fn make_ply(&mut self, _boards: &Bitboards, _ply: &Ply) {}
+
+    /// Called after `ply` has been unmade on `boards`
+    fn unmake_ply(&mut self, _boards: &Bitboards, _ply: &Ply) {}
+
+    /// Called after a turn has been passed on `boards`, only the side to move changed
+    fn make_null_ply(&mut self, _boards: &Bitboards) {}
+
+    /// Called after a passed turn has been taken back on `boards`
+    fn unmake_null_ply(&mut self, _boards: &Bitboards) {}
+}
+
+/// Evaluation by `Weights`. Material and piece-square scores are kept up to date by the ply hooks
+/// once the evaluator has been reset, otherwise they are summed up on every evaluation.
+#[derive(Debug, Clone, Default)]
+pub struct ClassicalEvaluator {
+    pub weights: Weights,
+    /// Sums of the position the evaluator was reset with, updated by every ply since
+    pieces: Option<PieceTerms>,
+    area: BoardArea,
+}
+
+impl From<Weights> for ClassicalEvaluator {
+    fn from(weights: Weights) -> Self {
+        Self {
+            weights,
+            ..Default::default()
+        }
+    }
+}
+
+/// Scores summed over all pieces, for white minus black
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct PieceTerms {
+    material: i32,
+    midgame: i32,
+    endgame: i32,
+    /// Game phase by the pieces of both colors, see `phase_weight`
+    phase: i32,
+}
+
+impl PieceTerms {
+    fn new(weights: &Weights, area: &BoardArea, boards: &Bitboards) -> Self {
+        let mut terms = Self::default();
+        for (piece, tile) in boards.key_value_pieces_iter() {
+            terms.add(weights, area, piece, tile, 1);
+        }
+        terms
+    }
+
+    /// Adds (`sign` 1) or removes (`sign` -1) a piece on `tile`
+    fn add(
+        &mut self,
+        weights: &Weights,
+        area: &BoardArea,
+        piece: Piece,
+        tile: BitIndex,
+        sign: i32,
+    ) {
+        let Piece(piece_type, color) = piece;
+        let signed = sign * color.score_sign();
+        let (midgame, endgame) = weights.square_weights(piece_type).score(area, tile, color);
+        self.material += signed * weights.piece_value(piece_type);
+        self.midgame += signed * midgame;
+        self.endgame += signed * endgame;
+        self.phase += sign * phase_weight(piece_type);
+    }
+
+    /// Applies the piece changes of `ply`, undoing them with `sign` -1
+    fn apply(&mut self, weights: &Weights, area: &BoardArea, ply: &Ply, sign: i32) {
+        self.add(weights, area, ply.moving_piece, ply.from, -sign);
+        let landing_piece = ply.promoting.unwrap_or(ply.moving_piece);
+        self.add(weights, area, landing_piece, ply.to, sign);
+        if let Some((captured, idx)) = ply.capturing {
+            self.add(weights, area, captured, idx, -sign);
+        }
+        if let Some((other_piece, from, to)) = ply.also_move {
+            self.add(weights, area, other_piece, from, -sign);
+            self.add(weights, area, other_piece, to, sign);
+        }
+    }
+}
+
+impl Evaluator for ClassicalEvaluator {
+    fn evaluate(&self, boards: &Bitboards) -> i32 {
+        // Material and piece-square score, the latter tapered from midgame to endgame by the
+        // non-pawn material left
+        let area = BoardArea::new(boards.limits);
+        let pieces = self
+            .pieces
+            .unwrap_or_else(|| PieceTerms::new(&self.weights, &area, boards));
+        let square_score = taper(pieces.midgame, pieces.endgame, pieces.phase);
+
+        // Pawn structure score
+        let pawns = boards.pawn_structure(&area);
+        let pawn_score = self.weights.isolated_pawn * pawns.isolated
+            + self.weights.doubled_pawn * pawns.doubled
+            + self.weights.backward_pawn * pawns.backward
+            + self.weights.connected_pawn * pawns.connected
+            + self.weights.passed_pawn * pawns.passed
+            + area.scale_advance(self.weights.passed_pawn_advance * pawns.passed_advance);
+
+        // King safety score, only weighed while enough pieces are left to attack the king
+        let king = boards.king_safety();
+        let king_score = taper(
+            self.weights.king_shield * king.shield
+                + self.weights.king_open_file * king.open_files
+                + self.weights.king_zone_attack * king.zone_attacks,
+            0,
+            pieces.phase,
+        );
+
+        // Move score
+        let move_score = boards.mobility_by_color(PieceColor::White) as i32
+            - boards.mobility_by_color(PieceColor::Black) as i32;
+
+        pieces.material
+            + square_score
+            + pawn_score
+            + king_score
+            + (self.weights.movement * move_score)
+    }
+
+    fn piece_value(&self, piece_type: PieceType) -> i32 {
+        self.weights.piece_value(piece_type)
+    }
+
+    fn reset(&mut self, boards: &Bitboards) {
+        self.area = BoardArea::new(boards.limits);
+        self.pieces = Some(PieceTerms::new(&self.weights, &self.area, boards));
+    }
+
+    fn make_ply(&mut self, _boards: &Bitboards, ply: &Ply) {
+        if let Some(pieces) = self.pieces.as_mut() {
+            pieces.apply(&self.weights, &self.area, ply, 1);
+        }
+    }
+
+    fn unmake_ply(&mut self, _boards: &Bitboards, ply: &Ply) {
+        if let Some(pieces) = self.pieces.as_mut() {
+            pieces.apply(&self.weights, &self.area, ply, -1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::chess_engine::{bitboard::SearchLimits, game::Game, zobrist::ZobristHash};
+
+    use super::*;
+
+    #[test]
+    fn incremental_matches_scratch() {
+        let (mut boards, _) = Bitboards::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let mut evaluator = ClassicalEvaluator::default();
+        evaluator.reset(&boards);
+        let scratch = ClassicalEvaluator::default();
+
+        let plys: Vec<Ply> = boards.all_legal_plys_by_color(PieceColor::White);
+        for ply in plys {
+            boards.make_ply(&ply);
+            evaluator.make_ply(&boards, &ply);
+            assert_eq!(evaluator.evaluate(&boards), scratch.evaluate(&boards));
+
+            let replies: Vec<Ply> = boards.all_legal_plys_by_color(PieceColor::Black);
+            for reply in replies {
+                boards.make_ply(&reply);
+                evaluator.make_ply(&boards, &reply);
+                assert_eq!(
+                    evaluator.pieces,
+                    Some(PieceTerms::new(
+                        &evaluator.weights,
+                        &evaluator.area,
+                        &boards
+                    )),
+                    "incremental terms drifted after {ply:?} {reply:?}"
+                );
+                boards.unmake_ply(&reply);
+                evaluator.unmake_ply(&boards, &reply);
+            }
+
+            boards.unmake_ply(&ply);
+            evaluator.unmake_ply(&boards, &ply);
+        }
+        assert_eq!(
+            evaluator.pieces,
+            Some(PieceTerms::new(
+                &evaluator.weights,
+                &evaluator.area,
+                &boards
+            ))
+        );
+    }
+
+    /// Evaluator following the hashes of the positions it was told about, which have to match
+    /// the evaluated positions
+    #[derive(Clone, Default)]
+    struct TrackingEvaluator {
+        hashes: Vec<ZobristHash>,
+        /// Passed turns seen by all clones
+        null_plys: Arc<AtomicUsize>,
+    }
+
+    impl Evaluator for TrackingEvaluator {
+        fn evaluate(&self, boards: &Bitboards) -> i32 {
+            assert_eq!(self.hashes.last(), Some(&boards.zobrist_hash));
+            0
+        }
+
+        fn piece_value(&self, _piece_type: PieceType) -> i32 {
+            1
+        }
+
+        fn reset(&mut self, boards: &Bitboards) {
+            self.hashes = vec![boards.zobrist_hash];
+        }
+
+        fn make_ply(&mut self, boards: &Bitboards, _ply: &Ply) {
+            self.hashes.push(boards.zobrist_hash);
+        }
+
+        fn unmake_ply(&mut self, boards: &Bitboards, _ply: &Ply) {
+            self.hashes.pop();
+            assert_eq!(self.hashes.last(), Some(&boards.zobrist_hash));
+        }
+
+        fn make_null_ply(&mut self, boards: &Bitboards) {
+            self.null_plys.fetch_add(1, Ordering::Relaxed);
+            self.hashes.push(boards.zobrist_hash);
+        }
+
+        fn unmake_null_ply(&mut self, boards: &Bitboards) {
+            self.hashes.pop();
+            assert_eq!(self.hashes.last(), Some(&boards.zobrist_hash));
+        }
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn search_with_custom_evaluator() {
+        let mut boards = Game::default().boards;
+        let limits = SearchLimits {
+            threads: 2,
+            ..SearchLimits::depth(5)
+        };
+        let evaluator = TrackingEvaluator::default();
+        let null_plys = evaluator.null_plys.clone();
+        let (score, pv, _) =
+            boards.search_with_limits(PieceColor::White, &limits, evaluator, &mut |_| {});
+        assert_eq!(score, 0);
+        assert!(!pv.is_empty());
+        assert!(null_plys.load(Ordering::Relaxed) > 0);
+    }
+}
@@ -0,0 +1,160 @@
+//! Ordering of quiet plys by how often similar plys caused beta cutoffs before
+
+use crate::chess_engine::{bitboard::Ply, pieces::PieceColor};
+
+use super::PlyKey;
+
+/// Killer plys remembered per tree height
+pub(super) const KILLER_SLOTS: usize = 2;
+/// Tiles of the largest board
+const BOARD_TILES: usize = 256;
+/// History scores are halved once one exceeds this, keeping recent cutoffs relevant
+const HISTORY_MAX: u32 = 1 << 20;
+/// Ordering score of the most recent killer, the older killers follow below it
+const KILLER_SCORE: u32 = HISTORY_MAX + KILLER_SLOTS as u32;
+
+/// Quiet plys which caused a beta cutoff at the same tree height, most recent first
+#[derive(Debug, Default)]
+pub(super) struct Killers(Vec<[Option<PlyKey>; KILLER_SLOTS]>);
+
+impl Killers {
+    pub(super) fn store(&mut self, height: usize, ply: &Ply) {
+        if self.0.len() <= height {
+            self.0.resize(height + 1, Default::default());
+        }
+        let key = PlyKey::from(ply);
+        let slots = &mut self.0[height];
+        if slots[0] != Some(key) {
+            slots.rotate_right(1);
+            slots[0] = Some(key);
+        }
+    }
+
+    /// Killers at `height`, most recent first
+    pub(super) fn at(&self, height: usize) -> [Option<PlyKey>; KILLER_SLOTS] {
+        self.0.get(height).copied().unwrap_or_default()
+    }
+
+    /// Slot of `ply` among the killers at `height`, 0 being the most recent
+    pub(super) fn slot(&self, height: usize, ply: &Ply) -> Option<usize> {
+        self.0
+            .get(height)?
+            .iter()
+            .position(|killer| killer.is_some_and(|killer| killer.matches(ply)))
+    }
+}
+
+/// Butterfly table of cutoff scores of quiet plys, indexed by color, from and to tile
+#[derive(Debug)]
+pub(super) struct History(Vec<u32>);
+
+impl Default for History {
+    fn default() -> Self {
+        Self(vec![0; 2 * BOARD_TILES * BOARD_TILES])
+    }
+}
+
+impl History {
+    fn index(ply: &Ply) -> usize {
+        let color = match ply.moving_piece.1 {
+            PieceColor::White => 0,
+            PieceColor::Black => 1,
+        };
+        (color * BOARD_TILES + *ply.from as usize) * BOARD_TILES + *ply.to as usize
+    }
+
+    /// Rewards a cutoff of `ply`, deeper searches weighing more
+    pub(super) fn reward(&mut self, ply: &Ply, depth: i8) {
+        let bonus = (depth.max(1) as u32).pow(2);
+        let score = &mut self.0[Self::index(ply)];
+        *score += bonus;
+        if *score > HISTORY_MAX {
+            self.0.iter_mut().for_each(|score| *score /= 2);
+        }
+    }
+
+    pub(super) fn score(&self, ply: &Ply) -> u32 {
+        self.0[Self::index(ply)]
+    }
+}
+
+/// Ordering score of a quiet ply, killers first, then by history
+pub(super) fn quiet_score(killers: &Killers, history: &History, height: usize, ply: &Ply) -> i32 {
+    match killers.slot(height, ply) {
+        Some(slot) => (KILLER_SCORE - slot as u32) as i32,
+        None => history.score(ply) as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chess_engine::pieces::{BLACK_KNIGHT, WHITE_KNIGHT};
+
+    use super::*;
+
+    fn ply(from: u32, to: u32) -> Ply {
+        Ply {
+            moving_piece: WHITE_KNIGHT,
+            from: from.into(),
+            to: to.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn killers_most_recent_first() {
+        let mut killers = Killers::default();
+        assert_eq!(killers.slot(3, &ply(1, 2)), None);
+
+        killers.store(3, &ply(1, 2));
+        killers.store(3, &ply(4, 5));
+        killers.store(3, &ply(4, 5));
+        assert_eq!(killers.slot(3, &ply(4, 5)), Some(0));
+        assert_eq!(killers.slot(3, &ply(1, 2)), Some(1));
+        assert_eq!(killers.slot(2, &ply(1, 2)), None);
+
+        killers.store(3, &ply(6, 7));
+        assert_eq!(killers.slot(3, &ply(1, 2)), None);
+    }
+
+    #[test]
+    fn history_by_color_and_depth() {
+        let mut history = History::default();
+        history.reward(&ply(254, 255), 3);
+        history.reward(&ply(254, 255), 1);
+        assert_eq!(history.score(&ply(254, 255)), 10);
+
+        let black = Ply {
+            moving_piece: BLACK_KNIGHT,
+            ..ply(254, 255)
+        };
+        assert_eq!(history.score(&black), 0);
+    }
+
+    #[test]
+    fn history_halves_when_saturated() {
+        let mut history = History::default();
+        history.reward(&ply(1, 2), 2);
+        for _ in 0..(HISTORY_MAX / 64 + 1) {
+            history.reward(&ply(3, 4), 8);
+        }
+        assert!(history.score(&ply(3, 4)) <= HISTORY_MAX);
+        assert_eq!(history.score(&ply(1, 2)), 2);
+    }
+
+    #[test]
+    fn killers_before_history() {
+        let mut killers = Killers::default();
+        let mut history = History::default();
+        for _ in 0..100 {
+            history.reward(&ply(1, 2), 10);
+        }
+        killers.store(0, &ply(3, 4));
+        killers.store(0, &ply(5, 6));
+        let scores: Vec<i32> = [ply(5, 6), ply(3, 4), ply(1, 2), ply(7, 8)]
+            .iter()
+            .map(|ply| quiet_score(&killers, &history, 0, ply))
+            .collect();
+        assert!(scores.is_sorted_by(|a, b| a > b));
+    }
+}
@@ -0,0 +1,113 @@
+//! King safety features: the pawn shield in front of the king, columns next to the king without
+//! pawns of its color, and tiles of the king zone the opponent attacks.
+//! The king zone is the king's tile and the tiles it could step to.
+
+use ethnum::u256;
+use strum::IntoEnumIterator;
+
+use crate::chess_engine::{
+    bitboard::{Bitboards, bitboard_idx, move_gen::attacks::KING_ATTACKS},
+    pieces::{Piece, PieceColor, PieceType},
+};
+
+use super::pawn_structure::{file_fill, forward, sideways};
+
+/// King safety features, counted for white minus black
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KingSafety {
+    /// Pawns on the two ranks ahead of the king, on its own and the neighbouring columns
+    pub shield: i32,
+    /// Columns of and next to the king without pawns of its color
+    pub open_files: i32,
+    /// Tiles of the king zone attacked by the opponent
+    pub zone_attacks: i32,
+}
+
+impl Bitboards {
+    pub(super) fn king_safety(&self) -> KingSafety {
+        let mut safety = KingSafety::default();
+        for color in PieceColor::iter() {
+            let pawns = self.boards[bitboard_idx(Piece(PieceType::Pawn, color))];
+            let attacked = self.en_prise_by_color(color.next());
+            for king in self.boards[bitboard_idx(Piece(PieceType::King, color))].split() {
+                let columns = (king | sideways(king)) & self.limits;
+                let ahead = forward(columns, color);
+                let shield = (ahead | forward(ahead, color)) & pawns;
+                let open_files = columns
+                    .split()
+                    .filter(|column| *(file_fill(*column) & pawns) == u256::ZERO)
+                    .count();
+                let zone = (king | king.attacks_from(&KING_ATTACKS)) & self.limits;
+
+                safety.shield += color.score_sign() * shield.count_ones() as i32;
+                safety.open_files += color.score_sign() * open_files as i32;
+                safety.zone_attacks += color.score_sign() * (zone & attacked).count_ones() as i32;
+            }
+        }
+        safety
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shield_and_open_files() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            K0000000000
+            P0000000000
+            0P000000000
+            00000000000
+            00000000000
+            0000000000p
+            00000000pp0
+            000000000k0
+            "#,
+        );
+        let safety = boards.king_safety();
+        // white shelters behind three pawns, black behind two on the edge of the board,
+        // the second rank ahead counts as well
+        assert_eq!(safety.shield, 3 - 2);
+        // no open column around either king
+        assert_eq!(safety.open_files, 0);
+    }
+
+    #[test]
+    fn open_files_next_to_king() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            0000K0000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000p0p000
+            0000k0000
+            "#,
+        );
+        let safety = boards.king_safety();
+        // the white king's column is open, all three columns of the black king are
+        assert_eq!(safety.open_files, 1 - 3);
+        assert_eq!(safety.shield, 2);
+    }
+
+    #[test]
+    fn zone_attacks_by_opponent() {
+        let boards = Bitboards::new_from_str(
+            r#"
+            000K0
+            00000
+            00000
+            0000R
+            0k000
+            "#,
+        );
+        let safety = boards.king_safety();
+        // the black rook sweeps the rank in front of the white king,
+        // nothing reaches the black king's zone
+        assert_eq!(safety.zone_attacks, 3);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use super::SearchConfig;
+
+/// Moves assumed to remain until the next time control when none is given
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// Time kept in reserve for communication and move application
+const MOVE_OVERHEAD: Duration = Duration::from_millis(20);
+/// Multiple of the soft budget a single iteration may run into before it is aborted
+const HARD_LIMIT_FACTOR: u32 = 4;
+
+/// Shareable flag to cancel a running search from another thread
+#[derive(Debug, Clone, Default)]
+pub struct StopFlag(Arc<AtomicBool>);
+impl StopFlag {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Remaining time on the clock of the side to move
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Clock {
+    pub remaining: Duration,
+    /// Added to `remaining` after every move
+    pub increment: Duration,
+    /// Moves until the next time control, if any
+    pub moves_to_go: Option<u32>,
+}
+
+/// Limits of a search, whichever is reached first ends it.
+/// Without any limit the search only ends through the `stop` flag.
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    /// Maximum iterative deepening depth
+    pub depth: Option<i8>,
+    /// Maximum count of visited nodes
+    pub nodes: Option<u64>,
+    /// Fixed wall clock budget for this move
+    pub movetime: Option<Duration>,
+    /// Budget derived from the game clock
+    pub clock: Option<Clock>,
+    /// Searching threads including the calling one, which reports the result. 0 counts as 1
+    pub threads: usize,
+    /// Selective techniques used by the search
+    pub config: SearchConfig,
+    pub stop: StopFlag,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: i8) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn movetime(movetime: Duration) -> Self {
+        Self {
+            movetime: Some(movetime),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_threads(self, threads: usize) -> Self {
+        Self { threads, ..self }
+    }
+
+    pub fn with_config(self, config: SearchConfig) -> Self {
+        Self { config, ..self }
+    }
+
+    /// Soft and hard time budget. No new iteration is started after the soft budget,
+    /// a running iteration is aborted after the hard budget.
+    pub fn time_budget(&self) -> (Option<Duration>, Option<Duration>) {
+        let clock_budget = self.clock.map(|clock| {
+            let available = clock.remaining.saturating_sub(MOVE_OVERHEAD);
+            let moves_to_go = clock.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+            let soft = (available / moves_to_go + clock.increment * 3 / 4).min(available);
+            let hard = (soft * HARD_LIMIT_FACTOR).min(available);
+            (soft, hard)
+        });
+
+        let movetime = self
+            .movetime
+            .map(|movetime| movetime.saturating_sub(MOVE_OVERHEAD));
+        match (clock_budget, movetime) {
+            (Some((soft, hard)), Some(movetime)) => {
+                (Some(soft.min(movetime)), Some(hard.min(movetime)))
+            }
+            (Some((soft, hard)), None) => (Some(soft), Some(hard)),
+            (None, Some(movetime)) => (Some(movetime), Some(movetime)),
+            (None, None) => (None, None),
+        }
+    }
+
+    /// Soft and hard deadlines for a search started at `start`
+    pub(super) fn deadlines(&self, start: Instant) -> (Option<Instant>, Option<Instant>) {
+        let (soft, hard) = self.time_budget();
+        (soft.map(|soft| start + soft), hard.map(|hard| start + hard))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_time_budget_without_time_limits() {
+        assert_eq!(SearchLimits::depth(5).time_budget(), (None, None));
+    }
+
+    #[test]
+    fn movetime_budget() {
+        let limits = SearchLimits::movetime(Duration::from_millis(1000));
+        let budget = Some(Duration::from_millis(1000) - MOVE_OVERHEAD);
+        assert_eq!(limits.time_budget(), (budget, budget));
+    }
+
+    #[test]
+    fn clock_budget_with_increment() {
+        let limits = SearchLimits {
+            clock: Some(Clock {
+                remaining: Duration::from_millis(60_020),
+                increment: Duration::from_millis(1000),
+                moves_to_go: None,
+            }),
+            ..Default::default()
+        };
+        let (soft, hard) = limits.time_budget();
+        assert_eq!(soft, Some(Duration::from_millis(2000 + 750)));
+        assert_eq!(hard, Some(Duration::from_millis(11_000)));
+    }
+
+    #[test]
+    fn clock_budget_never_exceeds_remaining_time() {
+        let limits = SearchLimits {
+            clock: Some(Clock {
+                remaining: Duration::from_millis(520),
+                increment: Duration::from_millis(2000),
+                moves_to_go: Some(1),
+            }),
+            ..Default::default()
+        };
+        let (soft, hard) = limits.time_budget();
+        assert_eq!(soft, Some(Duration::from_millis(500)));
+        assert_eq!(hard, Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn movetime_caps_clock_budget() {
+        let limits = SearchLimits {
+            movetime: Some(Duration::from_millis(120)),
+            clock: Some(Clock {
+                remaining: Duration::from_secs(600),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let budget = Some(Duration::from_millis(100));
+        assert_eq!(limits.time_budget(), (budget, budget));
+    }
+
+    #[test]
+    fn stop_flag_is_shared_between_clones() {
+        let stop = StopFlag::default();
+        let shared = stop.clone();
+        assert!(!shared.is_stopped());
+        stop.stop();
+        assert!(shared.is_stopped());
+    }
+}
@@ -0,0 +1,237 @@
+//! Staged generation of the plys of a search node: the stored best ply first, then captures and
+//! promotions by exchange gain, killers, quiet plys by history and noisy plys losing material last.
+//! A stage is only generated once the earlier ones are exhausted, so early cutoffs skip most of it.
+
+use crate::chess_engine::{
+    bitboard::{Bitboards, MoveList, Ply, PlyFilter},
+    pieces::PieceColor,
+};
+
+use super::{
+    Evaluator, PlyKey, SearchMeta,
+    heuristics::{KILLER_SLOTS, quiet_score},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    StoredPly,
+    GenerateNoisy,
+    GoodNoisy,
+    Killers,
+    GenerateQuiet,
+    Quiet,
+    BadNoisy,
+    Done,
+}
+
+pub(super) struct MovePicker {
+    color: PieceColor,
+    height: usize,
+    stage: Stage,
+    stored_ply: Option<PlyKey>,
+    /// Plys returned by the stored ply and killer stages, skipped once generated again
+    tried: [Option<PlyKey>; 1 + KILLER_SLOTS],
+    killer_slot: usize,
+    plys: MoveList,
+    /// Plys before this index have been returned, or are the losing noisy plys
+    next: usize,
+    /// Losing noisy plys, moved to the front of `plys` while the quiet plys are picked
+    bad_noisy: usize,
+}
+
+impl MovePicker {
+    pub(super) fn new(color: PieceColor, height: usize, stored_ply: Option<PlyKey>) -> Self {
+        Self {
+            color,
+            height,
+            stage: Stage::StoredPly,
+            stored_ply,
+            tried: Default::default(),
+            killer_slot: 0,
+            plys: MoveList::default(),
+            next: 0,
+            bad_noisy: 0,
+        }
+    }
+
+    /// The next legal ply to search, generating the next stage when needed
+    pub(super) fn next<E: Evaluator>(
+        &mut self,
+        boards: &mut Bitboards,
+        meta: &SearchMeta<E>,
+    ) -> Option<Ply> {
+        loop {
+            match self.stage {
+                Stage::StoredPly => {
+                    self.stage = Stage::GenerateNoisy;
+                    if let Some(ply) = self
+                        .stored_ply
+                        .and_then(|key| boards.legal_ply(self.color, key, PlyFilter::All))
+                    {
+                        self.tried[0] = self.stored_ply;
+                        return Some(ply);
+                    }
+                }
+                Stage::GenerateNoisy => {
+                    boards.legal_plys_by_color(self.color, PlyFilter::Noisy, &mut self.plys);
+                    for ply in self.plys.iter_mut() {
+                        ply.order_score = boards.static_exchange(ply, &meta.evaluator);
+                    }
+                    self.stage = Stage::GoodNoisy;
+                }
+                Stage::GoodNoisy => {
+                    let end = self.plys.len();
+                    if self
+                        .select_best(end)
+                        .is_some_and(|ply| ply.order_score >= 0)
+                    {
+                        if let Some(ply) = self.take_untried() {
+                            return Some(ply);
+                        }
+                    } else {
+                        // Only losing plys remain, they wait in front of the quiet plys
+                        self.plys.copy_within(self.next..end, 0);
+                        self.bad_noisy = end - self.next;
+                        self.plys.truncate(self.bad_noisy);
+                        self.next = self.bad_noisy;
+                        self.stage = Stage::Killers;
+                    }
+                }
+                Stage::Killers => {
+                    let Some(key) = meta.killers.at(self.height).get(self.killer_slot).copied()
+                    else {
+                        self.stage = Stage::GenerateQuiet;
+                        continue;
+                    };
+                    self.killer_slot += 1;
+                    if let Some(key) = key
+                        && !self.tried.contains(&Some(key))
+                        && let Some(ply) = boards.legal_ply(self.color, key, PlyFilter::Quiet)
+                    {
+                        self.tried[self.killer_slot] = Some(key);
+                        return Some(ply);
+                    }
+                }
+                Stage::GenerateQuiet => {
+                    boards.legal_plys_by_color(self.color, PlyFilter::Quiet, &mut self.plys);
+                    for ply in self.plys[self.bad_noisy..].iter_mut() {
+                        ply.order_score =
+                            quiet_score(&meta.killers, &meta.history, self.height, ply);
+                    }
+                    self.stage = Stage::Quiet;
+                }
+                Stage::Quiet => {
+                    if self.select_best(self.plys.len()).is_none() {
+                        self.next = 0;
+                        self.stage = Stage::BadNoisy;
+                    } else if let Some(ply) = self.take_untried() {
+                        return Some(ply);
+                    }
+                }
+                Stage::BadNoisy => {
+                    if self.select_best(self.bad_noisy).is_none() {
+                        self.stage = Stage::Done;
+                    } else if let Some(ply) = self.take_untried() {
+                        return Some(ply);
+                    }
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// Swaps the highest ordered ply among the plys from `next` until `end` to `next`
+    fn select_best(&mut self, end: usize) -> Option<&Ply> {
+        let best = (self.next..end).max_by(|&a, &b| self.plys[a].cmp(&self.plys[b]))?;
+        self.plys.swap(self.next, best);
+        Some(&self.plys[self.next])
+    }
+
+    /// Returns the ply at `next` and moves past it, unless it was already returned earlier
+    fn take_untried(&mut self) -> Option<Ply> {
+        let ply = self.plys[self.next];
+        self.next += 1;
+        (!self.tried.contains(&Some(PlyKey::from(&ply)))).then_some(ply)
+    }
+}
+
+impl Bitboards {
+    /// The legal ply of `color` matching `key` and kept by `filter`, only the plys of the piece
+    /// on its origin are generated
+    fn legal_ply(&mut self, color: PieceColor, key: PlyKey, filter: PlyFilter) -> Option<Ply> {
+        let piece = self.piece_at(key.from).filter(|piece| piece.1 == color)?;
+        let mut plys: MoveList = MoveList::default();
+        self.legal_plys_of_piece(piece, key.from, filter, &mut plys);
+        plys.iter().find(|ply| key.matches(ply)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chess_engine::{bitboard::Bitboards, pieces::WHITE_QUEEN};
+
+    use super::*;
+
+    fn picked(boards: &mut Bitboards, meta: &SearchMeta, stored_ply: Option<PlyKey>) -> Vec<Ply> {
+        let mut picker = MovePicker::new(PieceColor::White, 0, stored_ply);
+        std::iter::from_fn(|| picker.next(boards, meta)).collect()
+    }
+
+    #[test]
+    fn picks_every_legal_ply_once() {
+        let (mut boards, _) = Bitboards::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let meta: SearchMeta = SearchMeta::default();
+        let legal: Vec<Ply> = boards.all_legal_plys_by_color(PieceColor::White);
+
+        let stored = legal.iter().find(|ply| ply.capturing.is_none()).unwrap();
+        let plys = picked(&mut boards, &meta, Some(PlyKey::from(stored)));
+        assert_eq!(plys.len(), legal.len());
+        assert!(
+            legal
+                .iter()
+                .all(|ply| plys.iter().any(|picked| picked.from == ply.from
+                    && picked.to == ply.to
+                    && picked.promoting == ply.promoting))
+        );
+        assert!(PlyKey::from(stored).matches(&plys[0]));
+    }
+
+    #[test]
+    fn orders_by_stage() {
+        // Qxb3 wins a rook, Qxd3 loses the queen to the pawn
+        let (mut boards, _) = Bitboards::from_fen("4k3/8/8/8/4p3/1r1p4/8/3Q3K w - - 0 1").unwrap();
+        let mut meta: SearchMeta = SearchMeta::default();
+        let killer = Ply {
+            moving_piece: WHITE_QUEEN,
+            from: boards.parse_square("d1").unwrap(),
+            to: boards.parse_square("d2").unwrap(),
+            ..Default::default()
+        };
+        meta.killers.store(0, &killer);
+
+        let plys = picked(&mut boards, &meta, None);
+        let first = plys.first().unwrap();
+        assert_eq!(first.to, boards.parse_square("b3").unwrap());
+        assert!(first.capturing.is_some());
+        assert!(PlyKey::from(&killer).matches(&plys[1]));
+        assert!(plys.last().unwrap().capturing.is_some());
+        assert!(plys.last().unwrap().order_score < 0);
+    }
+
+    #[test]
+    fn stored_ply_must_be_legal() {
+        let (mut boards, _) = Bitboards::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let meta: SearchMeta = SearchMeta::default();
+        let illegal = PlyKey {
+            from: boards.parse_square("e1").unwrap(),
+            to: boards.parse_square("e3").unwrap(),
+            promoting: None,
+        };
+        let plys = picked(&mut boards, &meta, Some(illegal));
+        assert_eq!(plys.len(), 5);
+        assert!(plys.iter().all(|ply| !illegal.matches(ply)));
+    }
+}
@@ -0,0 +1,344 @@
+//! Pawn structure features: doubled, isolated, backward, connected and passed pawns.
+//! The features only depend on the pawns, so they are cached under the pawn-only hash and stay
+//! valid when the weights change. Any board width up to 16 columns is handled by the shifts,
+//! as tiles outside the limits never hold pawns.
+
+use ethnum::u256;
+
+use crate::chess_engine::{
+    bitboard::{Bitboard, Bitboards, LocklessTable, bitboard_idx},
+    pieces::{Piece, PieceColor, PieceType},
+};
+
+use super::piece_square::BoardArea;
+
+/// Slots of the pawn structure cache
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+/// Every tile of the first column
+const FIRST_COLUMN: Bitboard = Bitboard(u256::from_words(
+    0x0001_0001_0001_0001_0001_0001_0001_0001,
+    0x0001_0001_0001_0001_0001_0001_0001_0001,
+));
+/// Every tile of the last column
+const LAST_COLUMN: Bitboard = Bitboard(u256::from_words(
+    0x8000_8000_8000_8000_8000_8000_8000_8000,
+    0x8000_8000_8000_8000_8000_8000_8000_8000,
+));
+
+/// Lockless cache of pawn structures, keyed by the pawn hash
+#[derive(Debug)]
+pub(in crate::chess_engine::bitboard) struct PawnTable(LocklessTable<2>);
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self(LocklessTable::with_capacity(PAWN_TABLE_SIZE))
+    }
+}
+
+/// Pawn structure features, counted for white minus black
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PawnStructure {
+    /// Pawns with another pawn of their color behind them on the same column
+    pub doubled: i32,
+    /// Pawns without pawns of their color on the neighbouring columns
+    pub isolated: i32,
+    /// Pawns which no neighbouring pawn can support, whose next tile is attacked by a pawn
+    pub backward: i32,
+    /// Pawns next to or defended by a pawn of their color
+    pub connected: i32,
+    /// Pawns without opposing pawns ahead on their own or the neighbouring columns
+    pub passed: i32,
+    /// Ranks advanced by the passed pawns
+    pub passed_advance: i32,
+}
+
+impl PawnStructure {
+    pub fn new(white_pawns: Bitboard, black_pawns: Bitboard, area: &BoardArea) -> Self {
+        let white = ColorStructure::new(white_pawns, black_pawns, PieceColor::White, area);
+        let black = ColorStructure::new(black_pawns, white_pawns, PieceColor::Black, area);
+        Self {
+            doubled: white.doubled - black.doubled,
+            isolated: white.isolated - black.isolated,
+            backward: white.backward - black.backward,
+            connected: white.connected - black.connected,
+            passed: white.passed - black.passed,
+            passed_advance: white.passed_advance - black.passed_advance,
+        }
+    }
+
+    fn to_words(self) -> [u64; 2] {
+        let pack = |values: [i32; 4]| {
+            values
+                .iter()
+                .rev()
+                .fold(0, |word, value| word << 16 | *value as i16 as u16 as u64)
+        };
+        [
+            pack([self.doubled, self.isolated, self.backward, self.connected]),
+            pack([self.passed, self.passed_advance, 0, 0]),
+        ]
+    }
+
+    fn from_words(words: [u64; 2]) -> Self {
+        let unpack = |word: u64, i: u32| (word >> (16 * i)) as u16 as i16 as i32;
+        Self {
+            doubled: unpack(words[0], 0),
+            isolated: unpack(words[0], 1),
+            backward: unpack(words[0], 2),
+            connected: unpack(words[0], 3),
+            passed: unpack(words[1], 0),
+            passed_advance: unpack(words[1], 1),
+        }
+    }
+}
+
+/// Features of the pawns of one color
+struct ColorStructure {
+    doubled: i32,
+    isolated: i32,
+    backward: i32,
+    connected: i32,
+    passed: i32,
+    passed_advance: i32,
+}
+
+impl ColorStructure {
+    fn new(pawns: Bitboard, opposing: Bitboard, color: PieceColor, area: &BoardArea) -> Self {
+        let ahead = |board| forward_fill(forward(board, color), color);
+        let behind = |board| forward_fill(forward(board, color.next()), color.next());
+
+        let neighbours = sideways(pawns);
+        let supporters = neighbours | pawn_attacks(pawns, color);
+        let isolated = pawns & !sideways(file_fill(pawns));
+
+        // Next tile attacked by an opposing pawn, while no neighbouring pawn stands level or behind
+        let stop_attacked = forward(pawns, color) & pawn_attacks(opposing, color.next());
+        let backward =
+            pawns & !forward_fill(neighbours, color) & forward(stop_attacked, color.next());
+
+        let passed = pawns & !behind(opposing | sideways(opposing));
+        let mut passed_advance = 0;
+        let mut remaining = *passed;
+        while remaining != 0 {
+            passed_advance += area.advance(remaining.trailing_zeros().into(), color);
+            remaining &= remaining - 1;
+        }
+
+        Self {
+            doubled: (pawns & ahead(pawns)).count_ones() as i32,
+            isolated: isolated.count_ones() as i32,
+            backward: backward.count_ones() as i32,
+            connected: (pawns & supporters).count_ones() as i32,
+            passed: passed.count_ones() as i32,
+            passed_advance,
+        }
+    }
+}
+
+/// Tiles one rank ahead in the moving direction of `color`, white moving towards the first row
+pub(super) fn forward(board: Bitboard, color: PieceColor) -> Bitboard {
+    match color {
+        PieceColor::White => board >> 16,
+        PieceColor::Black => board << 16,
+    }
+}
+
+/// The tiles of `board` and every tile ahead of them on their columns
+fn forward_fill(mut board: Bitboard, color: PieceColor) -> Bitboard {
+    for shift in [16, 32, 64, 128] {
+        board |= match color {
+            PieceColor::White => board >> shift,
+            PieceColor::Black => board << shift,
+        };
+    }
+    board
+}
+
+/// Every tile of the columns `board` has tiles on
+pub(super) fn file_fill(board: Bitboard) -> Bitboard {
+    forward_fill(board, PieceColor::White) | forward_fill(board, PieceColor::Black)
+}
+
+/// Tiles on both sides of the tiles of `board`, without wrapping around rows
+pub(super) fn sideways(board: Bitboard) -> Bitboard {
+    ((board & !LAST_COLUMN) << 1) | ((board & !FIRST_COLUMN) >> 1)
+}
+
+/// Tiles the pawns on `board` attack
+fn pawn_attacks(board: Bitboard, color: PieceColor) -> Bitboard {
+    sideways(forward(board, color))
+}
+
+impl Bitboards {
+    /// Pawn structure of the position, cached under the pawn hash
+    pub(super) fn pawn_structure(&self, area: &BoardArea) -> PawnStructure {
+        let key = *self.pawn_hash;
+        if let Some(words) = self.pawn_table.0.load(key) {
+            return PawnStructure::from_words(words);
+        }
+        let structure = PawnStructure::new(
+            self.boards[bitboard_idx(Piece(PieceType::Pawn, PieceColor::White))],
+            self.boards[bitboard_idx(Piece(PieceType::Pawn, PieceColor::Black))],
+            area,
+        );
+        self.pawn_table.0.store(key, structure.to_words());
+        structure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn structure(input: &str) -> PawnStructure {
+        let boards = Bitboards::new_from_str(input);
+        boards.pawn_structure(&BoardArea::new(boards.limits()))
+    }
+
+    #[test]
+    fn doubled_and_isolated_on_narrow_board() {
+        let structure = structure(
+            r#"
+            000
+            000
+            p00
+            p0P
+            "#,
+        );
+        assert_eq!(structure.doubled, 1);
+        // both white pawns are isolated, as is the black pawn
+        assert_eq!(structure.isolated, 1);
+        assert_eq!(structure.connected, 0);
+    }
+
+    #[test]
+    fn isolated_pawns_on_the_edge_columns_of_wide_board() {
+        let structure = structure(
+            r#"
+            0000000000000000
+            P00000000000000P
+            0000000000000000
+            0000000000000000
+            0p0p00000000pp00
+            0000000000000000
+            "#,
+        );
+        // the black pawns on the edges don't neighbour each other across rows
+        assert_eq!(structure.isolated, 2 - 2);
+        assert_eq!(structure.connected, 2);
+    }
+
+    #[test]
+    fn connected_by_phalanx_and_defence() {
+        let structure = structure(
+            r#"
+            00000
+            00000
+            0pp00
+            000p0
+            00000
+            "#,
+        );
+        // the phalanx on b and c, c is defended as well while the defender on d is not
+        assert_eq!(structure.connected, 2);
+        assert_eq!(structure.isolated, 0);
+    }
+
+    #[test]
+    fn passed_pawns_by_color() {
+        let structure = structure(
+            r#"
+            000000
+            0p0000
+            000000
+            0000P0
+            000000
+            p00000
+            "#,
+        );
+        // no pawn stands ahead of or next to another
+        assert_eq!(structure.passed, 2 - 1);
+        // 4 ranks advanced on b and none on a, against 3 ranks of the black pawn
+        assert_eq!(structure.passed_advance, 4 - 3);
+    }
+
+    #[test]
+    fn passed_pawn_held_up_by_neighbouring_column() {
+        let structure = structure(
+            r#"
+            0000000
+            00P0000
+            0000000
+            000p000
+            0000000
+            "#,
+        );
+        assert_eq!(structure.passed, 0);
+    }
+
+    #[test]
+    fn backward_pawn() {
+        let structure = structure(
+            r#"
+            00000000
+            00000000
+            00000P00
+            0000P000
+            00p00000
+            000p0000
+            00000000
+            "#,
+        );
+        // d can't advance past the black pawn's attack and its neighbour on c already moved on,
+        // the attacked black pawn is supported from behind
+        assert_eq!(structure.backward, 1);
+    }
+
+    #[test]
+    fn words_round_trip() {
+        let structure = PawnStructure {
+            doubled: -3,
+            isolated: 2,
+            backward: -1,
+            connected: 7,
+            passed: -2,
+            passed_advance: -25,
+        };
+        assert_eq!(PawnStructure::from_words(structure.to_words()), structure);
+    }
+
+    #[test]
+    fn cached_under_pawn_hash() {
+        let mut boards = Bitboards::new_from_str(
+            r#"
+            0000
+            0P00
+            0000
+            p00k
+            "#,
+        );
+        let area = BoardArea::new(boards.limits());
+        let before = boards.pawn_structure(&area);
+        let hash = boards.pawn_hash;
+
+        // moving the king keeps the pawn hash and the cached structure
+        let ply = boards
+            .all_legal_plys_by_color::<Vec<_>>(PieceColor::White)
+            .into_iter()
+            .find(|ply| ply.moving_piece.0 == PieceType::King)
+            .unwrap();
+        boards.make_ply(&ply);
+        assert_eq!(boards.pawn_hash, hash);
+        assert_eq!(boards.pawn_table.0.load(*hash), Some(before.to_words()));
+
+        boards.unmake_ply(&ply);
+        let ply = boards
+            .all_legal_plys_by_color::<Vec<_>>(PieceColor::White)
+            .into_iter()
+            .find(|ply| ply.moving_piece.0 == PieceType::Pawn)
+            .unwrap();
+        boards.make_ply(&ply);
+        assert_ne!(boards.pawn_hash, hash);
+    }
+}
@@ -0,0 +1,222 @@
+//! Piece-square tables relative to the active area of the board, so they fit any board size.
+//! Each piece type gets a bonus for standing in the centre and for having advanced towards the
+//! opposing back rank, scaled down linearly towards the edges and the own back rank.
+//! Midgame and endgame bonuses are interpolated by the non-pawn material left on the board.
+
+use crate::chess_engine::{
+    bitboard::{BitIndex, Bitboard},
+    pieces::{PieceColor, PieceType},
+};
+
+/// Phase of the non-pawn pieces of a standard army, larger armies count as midgame throughout
+pub const MIDGAME_PHASE: i32 = 24;
+
+/// Contribution of a piece type to the game phase
+pub fn phase_weight(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Queen => 4,
+        PieceType::Rook => 2,
+        PieceType::Bishop | PieceType::Knight => 1,
+        PieceType::King | PieceType::Pawn => 0,
+    }
+}
+
+/// Interpolates between a midgame and an endgame score, `phase` counting down to the endgame
+pub fn taper(midgame: i32, endgame: i32, phase: i32) -> i32 {
+    let phase = phase.clamp(0, MIDGAME_PHASE);
+    (midgame * phase + endgame * (MIDGAME_PHASE - phase)) / MIDGAME_PHASE
+}
+
+/// Piece-square weights of one piece type, bonuses on the most central tile and on the last rank
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SquareWeights {
+    pub centre_mg: i32,
+    pub centre_eg: i32,
+    pub advance_mg: i32,
+    pub advance_eg: i32,
+}
+
+impl SquareWeights {
+    /// Names of all weights, in declaration order
+    pub const NAMES: [&'static str; 4] = ["centre_mg", "centre_eg", "advance_mg", "advance_eg"];
+
+    /// Weight by its field name
+    pub fn get(&self, name: &str) -> Option<i32> {
+        match name {
+            "centre_mg" => Some(self.centre_mg),
+            "centre_eg" => Some(self.centre_eg),
+            "advance_mg" => Some(self.advance_mg),
+            "advance_eg" => Some(self.advance_eg),
+            _ => None,
+        }
+    }
+
+    /// Mutable access to a weight by its field name
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut i32> {
+        match name {
+            "centre_mg" => Some(&mut self.centre_mg),
+            "centre_eg" => Some(&mut self.centre_eg),
+            "advance_mg" => Some(&mut self.advance_mg),
+            "advance_eg" => Some(&mut self.advance_eg),
+            _ => None,
+        }
+    }
+
+    /// Midgame and endgame bonus of a piece of `color` on `tile`
+    pub fn score(&self, area: &BoardArea, tile: BitIndex, color: PieceColor) -> (i32, i32) {
+        let (centre, advance) = (area.centre(tile), area.advance(tile, color));
+        (
+            area.scale_centre(self.centre_mg * centre)
+                + area.scale_advance(self.advance_mg * advance),
+            area.scale_centre(self.centre_eg * centre)
+                + area.scale_advance(self.advance_eg * advance),
+        )
+    }
+}
+
+/// Bounding rows and columns of the active tiles
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BoardArea {
+    first_row: i32,
+    last_row: i32,
+    first_col: i32,
+    last_col: i32,
+}
+
+impl BoardArea {
+    pub fn new(limits: Bitboard) -> Self {
+        if *limits == 0 {
+            return Self {
+                first_row: 0,
+                last_row: 0,
+                first_col: 0,
+                last_col: 0,
+            };
+        }
+        let columns = limits.as_column_representation();
+        Self {
+            first_row: (limits.trailing_zeros() / 16) as i32,
+            last_row: ((255 - limits.leading_zeros()) / 16) as i32,
+            first_col: columns.trailing_zeros() as i32,
+            last_col: 15 - columns.leading_zeros() as i32,
+        }
+    }
+
+    /// Steps from the edges towards the centre, summed over rows and columns
+    fn centre(&self, tile: BitIndex) -> i32 {
+        let (row, col) = ((*tile / 16) as i32, (*tile % 16) as i32);
+        (row - self.first_row).min(self.last_row - row)
+            + (col - self.first_col).min(self.last_col - col)
+    }
+
+    /// Ranks advanced from the back rank of `color`, white moving towards the first row
+    pub(super) fn advance(&self, tile: BitIndex, color: PieceColor) -> i32 {
+        let row = (*tile / 16) as i32;
+        match color {
+            PieceColor::White => self.last_row - row,
+            PieceColor::Black => row - self.first_row,
+        }
+    }
+
+    fn scale_centre(&self, value: i32) -> i32 {
+        let most_central =
+            (self.last_row - self.first_row) / 2 + (self.last_col - self.first_col) / 2;
+        if most_central == 0 {
+            0
+        } else {
+            value / most_central
+        }
+    }
+
+    pub(super) fn scale_advance(&self, value: i32) -> i32 {
+        let last_rank = self.last_row - self.first_row;
+        if last_rank == 0 { 0 } else { value / last_rank }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chess_engine::bitboard::Bitboards;
+
+    use super::*;
+
+    fn area(input: &str) -> BoardArea {
+        BoardArea::new(Bitboards::new_from_str(input).limits())
+    }
+
+    #[test]
+    fn area_of_limits() {
+        let area = area(
+            r#"
+            000
+            000
+            000
+            000
+            "#,
+        );
+        assert_eq!(
+            area,
+            BoardArea {
+                first_row: 0,
+                last_row: 3,
+                first_col: 0,
+                last_col: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn centre_bonus_scales_with_board() {
+        let weights = SquareWeights {
+            centre_mg: 12,
+            ..Default::default()
+        };
+        let small = area(&"00000\n".repeat(5));
+        let large = area(&"0000000000000000\n".repeat(16));
+
+        // full bonus on the central tile, none on the edge
+        assert_eq!(
+            weights
+                .score(&small, (2 * 16 + 2).into(), PieceColor::White)
+                .0,
+            12
+        );
+        assert_eq!(weights.score(&small, 0.into(), PieceColor::White).0, 0);
+        assert_eq!(
+            weights
+                .score(&large, (7 * 16 + 8).into(), PieceColor::White)
+                .0,
+            12
+        );
+        assert_eq!(
+            weights.score(&large, (7 * 16).into(), PieceColor::White).0,
+            6
+        );
+    }
+
+    #[test]
+    fn advance_bonus_by_color() {
+        let weights = SquareWeights {
+            advance_eg: 30,
+            ..Default::default()
+        };
+        let area = area(&"00000000\n".repeat(6));
+        // white moves towards the first row, black towards the last
+        assert_eq!(weights.score(&area, 3.into(), PieceColor::White).1, 30);
+        assert_eq!(weights.score(&area, 3.into(), PieceColor::Black).1, 0);
+        assert_eq!(
+            weights
+                .score(&area, (5 * 16 + 3).into(), PieceColor::Black)
+                .1,
+            30
+        );
+    }
+
+    #[test]
+    fn taper_by_phase() {
+        assert_eq!(taper(10, 40, MIDGAME_PHASE), 10);
+        assert_eq!(taper(10, 40, 0), 40);
+        assert_eq!(taper(10, 40, MIDGAME_PHASE / 2), 25);
+        assert_eq!(taper(10, 40, 2 * MIDGAME_PHASE), 10);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::chess_engine::{
+    bitboard::{BitIndex, Bitboard, Bitboards, Ply, bitboard_idx},
+    pieces::{Piece, PieceColor, PieceType},
+};
+
+use super::Evaluator;
+
+/// Attackers are tried from the least to the most valuable
+const EXCHANGE_ORDER: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+/// Captures in an exchange, bounding the swap list
+const MAX_EXCHANGE_LENGTH: usize = 64;
+
+impl Bitboards {
+    /// Pieces of `color` among `occupied` attacking `target`, sliding attacks are blocked by `occupied`.
+    /// Every attack mask is symmetric, pawns attack from the tiles a pawn of the opponent would attack.
+    fn attackers_of(&self, target: BitIndex, color: PieceColor, occupied: Bitboard) -> Bitboard {
+        let target = Bitboard::from(target);
+        let off_board = !self.limits;
+        let pieces = |piece_type| self.boards[bitboard_idx(Piece(piece_type, color))] & occupied;
+
+        let straight = pieces(PieceType::Rook) | pieces(PieceType::Queen);
+        let diagonal = pieces(PieceType::Bishop) | pieces(PieceType::Queen);
+        (target.pawn_en_prise_mask(&off_board, color.next()) & pieces(PieceType::Pawn))
+            | (target.knight_en_prise_mask(&off_board, &occupied) & pieces(PieceType::Knight))
+            | (target.king_en_prise_mask(&off_board, &occupied) & pieces(PieceType::King))
+            | (target.rook_en_prise_mask(&off_board, &occupied) & straight)
+            | (target.bishop_en_prise_mask(&off_board, &occupied) & diagonal)
+    }
+
+    /// Static exchange evaluation: material won by `ply` when both sides keep recapturing on its
+    /// target with their least valuable attacker, each side stopping once recapturing loses.
+    /// Pieces uncovered by an earlier capture join the exchange.
+    pub fn static_exchange(&self, ply: &Ply, evaluator: &impl Evaluator) -> i32 {
+        let target = ply.to;
+        let mut occupied = self.all_pieces_by_color(PieceColor::White)
+            | self.all_pieces_by_color(PieceColor::Black);
+        occupied.set(ply.from, false);
+
+        let mut gain = [0; MAX_EXCHANGE_LENGTH];
+        if let Some((Piece(captured, _), idx)) = ply.capturing {
+            // En passant captures off the target tile
+            occupied.set(idx, false);
+            gain[0] = evaluator.piece_value(captured);
+        }
+        let mut on_target = ply.moving_piece.0;
+        if let Some(Piece(promoted, _)) = ply.promoting {
+            gain[0] += evaluator.piece_value(promoted) - evaluator.piece_value(PieceType::Pawn);
+            on_target = promoted;
+        }
+
+        let mut color = ply.moving_piece.1.next();
+        let mut depth = 0;
+        while depth + 1 < MAX_EXCHANGE_LENGTH {
+            let attackers = self.attackers_of(target, color, occupied);
+            let Some((attacker, attacker_board)) = EXCHANGE_ORDER.iter().find_map(|&piece_type| {
+                let board = attackers & self.boards[bitboard_idx(Piece(piece_type, color))];
+                (*board != 0).then_some((piece_type, board))
+            }) else {
+                break;
+            };
+
+            depth += 1;
+            // Score of the side to capture if it captures and the exchange then stops
+            gain[depth] = evaluator.piece_value(on_target) - gain[depth - 1];
+            if (-gain[depth - 1]).max(gain[depth]) < 0 {
+                break;
+            }
+            occupied.set(attacker_board.trailing_zeros().into(), false);
+            on_target = attacker;
+            color = color.next();
+        }
+
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
+        }
+        gain[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethnum::u256;
+
+    use super::*;
+    use crate::chess_engine::bitboard::{ClassicalEvaluator, Weights};
+
+    fn exchange(fen: &str, notation: &str) -> i32 {
+        let (mut boards, _) = Bitboards::from_fen(fen).unwrap();
+        let ply = boards
+            .ply_from_long_algebraic(PieceColor::White, notation)
+            .unwrap();
+        boards.static_exchange(&ply, &ClassicalEvaluator::default())
+    }
+
+    #[test]
+    fn undefended_capture() {
+        let weights = Weights::default();
+        assert_eq!(
+            exchange("4k3/8/8/4n3/8/8/8/4RK2 w - - 0 1", "e1e5"),
+            weights.knight
+        );
+    }
+
+    #[test]
+    fn queen_takes_defended_pawn() {
+        let weights = Weights::default();
+        assert_eq!(
+            exchange("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "e1e5"),
+            weights.pawn - weights.queen
+        );
+    }
+
+    #[test]
+    fn defender_declines_losing_recapture() {
+        // Recapturing the pawn with the queen would lose it to the rook
+        let weights = Weights::default();
+        assert_eq!(
+            exchange("3qk3/8/8/3p4/8/8/8/3RK3 w - - 0 1", "d1d5"),
+            weights.pawn - weights.rook
+        );
+        assert_eq!(
+            exchange("3qk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"),
+            weights.pawn
+        );
+    }
+
+    #[test]
+    fn x_ray_attacker_joins() {
+        let weights = Weights::default();
+        // without the second rook, the first is lost for a pawn
+        assert_eq!(
+            exchange("r6k/8/8/p7/8/8/R7/6K1 w - - 0 1", "a2a5"),
+            weights.pawn - weights.rook
+        );
+        assert_eq!(
+            exchange("r6k/8/8/p7/8/8/R7/R5K1 w - - 0 1", "a2a5"),
+            weights.pawn
+        );
+    }
+
+    #[test]
+    fn en_passant_exchange() {
+        let weights = Weights::default();
+        assert_eq!(
+            exchange("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"),
+            weights.pawn
+        );
+    }
+
+    #[test]
+    fn attackers_of_tile() {
+        let (boards, _) = Bitboards::from_fen("4k3/8/8/3p4/8/1B2NP2/8/3RK2Q w - - 0 1").unwrap();
+        let target = boards.parse_square("d5").unwrap();
+        let occupied = boards.all_pieces_by_color(PieceColor::White)
+            | boards.all_pieces_by_color(PieceColor::Black);
+        let attackers = boards.attackers_of(target, PieceColor::White, occupied);
+        // the queen is blocked by its own pawn
+        let expected = ["b3", "e3", "d1"]
+            .iter()
+            .fold(Bitboard(u256::ZERO), |board, square| {
+                board | Bitboard::from(boards.parse_square(square).unwrap())
+            });
+        assert_eq!(attackers, expected);
+    }
+}
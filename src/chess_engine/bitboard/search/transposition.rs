@@ -0,0 +1,301 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use strum::IntoEnumIterator;
+
+use crate::chess_engine::{
+    bitboard::{BitIndex, Ply, lockless::LocklessTable},
+    pieces::{Piece, PieceType},
+    zobrist::ZobristHash,
+};
+
+use super::{MATE_SCORE, MAX_MATE_DISTANCE};
+
+/// Relation of a stored score to the true score of the position
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// Score lies within the search window
+    #[default]
+    Exact,
+    /// Search failed high, the true score is at least this score
+    Lower,
+    /// Search failed low, the true score is at most this score
+    Upper,
+}
+
+/// Compact identification of a ply, matched against the legal plys of the stored position
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PlyKey {
+    pub from: BitIndex,
+    pub to: BitIndex,
+    pub promoting: Option<PieceType>,
+}
+impl From<&Ply> for PlyKey {
+    fn from(ply: &Ply) -> Self {
+        Self {
+            from: ply.from,
+            to: ply.to,
+            promoting: ply.promoting.map(|Piece(piece_type, _)| piece_type),
+        }
+    }
+}
+impl PlyKey {
+    pub fn matches(&self, ply: &Ply) -> bool {
+        *self == Self::from(ply)
+    }
+
+    fn pack(key: Option<Self>) -> u64 {
+        let Some(key) = key else {
+            return 0;
+        };
+        let promoting = key.promoting.map_or(0, |piece_type| piece_type as u64 + 1);
+        1 | (*key.from as u64) << 8 | (*key.to as u64) << 16 | promoting << 24
+    }
+
+    fn unpack(word: u64) -> Option<Self> {
+        if word & 0xFF == 0 {
+            return None;
+        }
+        let promoting = match (word >> 24) & 0xFF {
+            0 => None,
+            n => PieceType::iter().nth(n as usize - 1),
+        };
+        Some(Self {
+            from: (((word >> 8) & 0xFF) as u32).into(),
+            to: (((word >> 16) & 0xFF) as u32).into(),
+            promoting,
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TranspositionEntry {
+    pub hash: ZobristHash,
+    /// Remaining depth the position has been searched with, 0 for quiescence
+    pub depth: i8,
+    pub bound: Bound,
+    pub best_ply: Option<PlyKey>,
+    /// Score from the view of the side to move, mates counted from this position
+    pub score: i32,
+    /// Search generation the entry was written in
+    pub age: u8,
+}
+
+impl TranspositionEntry {
+    fn pack(&self) -> [u64; 2] {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        [
+            self.score as u32 as u64
+                | (self.depth as u8 as u64) << 32
+                | bound << 40
+                | (self.age as u64) << 48,
+            PlyKey::pack(self.best_ply),
+        ]
+    }
+
+    fn unpack(hash: u64, [data, ply]: [u64; 2]) -> Self {
+        let bound = match (data >> 40) & 0xFF {
+            1 => Bound::Lower,
+            2 => Bound::Upper,
+            _ => Bound::Exact,
+        };
+        Self {
+            hash: hash.into(),
+            depth: (data >> 32) as u8 as i8,
+            bound,
+            best_ply: PlyKey::unpack(ply),
+            score: data as u32 as i32,
+            age: (data >> 48) as u8,
+        }
+    }
+}
+
+/// Fixed capacity hash table of searched positions, indexed by their zobrist hash.
+/// Entries of an older search are replaced first, then those searched with less depth.
+/// Shareable between searching threads without locking.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    entries: LocklessTable<2>,
+    age: AtomicU8,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl TranspositionTable {
+    pub const DEFAULT_CAPACITY: usize = 1 << 16;
+    /// Memory used per entry
+    pub const ENTRY_SIZE: usize = 3 * size_of::<u64>();
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: LocklessTable::with_capacity(capacity),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    /// Number of occupied slots
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes all entries, e.g. between games
+    pub fn clear(&self) {
+        self.entries.clear();
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    /// Changes the capacity, dropping all entries
+    pub fn resize(&mut self, capacity: usize) {
+        *self = Self::with_capacity(capacity);
+    }
+
+    /// Marks the start of a new search, entries of previous searches become replaceable
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, hash: ZobristHash) -> Option<TranspositionEntry> {
+        self.entries
+            .load(*hash)
+            .map(|data| TranspositionEntry::unpack(*hash, data))
+    }
+
+    pub fn store(&self, entry: TranspositionEntry) {
+        let age = self.age.load(Ordering::Relaxed);
+        let replace = match self.entries.load_slot(*entry.hash) {
+            None => true,
+            Some((hash, data)) => {
+                let existing = TranspositionEntry::unpack(hash, data);
+                existing.age != age || entry.depth >= existing.depth
+            }
+        };
+        if replace {
+            self.entries
+                .store(*entry.hash, TranspositionEntry { age, ..entry }.pack());
+        }
+    }
+}
+
+/// Converts a mate score counted from the root into one counted from the node at `height`
+pub(super) fn score_to_table(score: i32, height: usize) -> i32 {
+    match score {
+        score if score > MATE_SCORE - MAX_MATE_DISTANCE => score + height as i32,
+        score if score < -(MATE_SCORE - MAX_MATE_DISTANCE) => score - height as i32,
+        score => score,
+    }
+}
+
+/// Inverse of `score_to_table`
+pub(super) fn score_from_table(score: i32, height: usize) -> i32 {
+    match score {
+        score if score > MATE_SCORE - MAX_MATE_DISTANCE => score - height as i32,
+        score if score < -(MATE_SCORE - MAX_MATE_DISTANCE) => score + height as i32,
+        score => score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash: u64, depth: i8) -> TranspositionEntry {
+        TranspositionEntry {
+            hash: hash.into(),
+            depth,
+            score: depth as i32,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let table = TranspositionTable::with_capacity(16);
+        assert!(table.is_empty());
+        table.store(entry(3, 2));
+        assert_eq!(table.probe(3.into()), Some(entry(3, 2)));
+        // same slot, different position
+        assert_eq!(table.probe(19.into()), None);
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn deeper_entries_are_kept_within_a_search() {
+        let table = TranspositionTable::with_capacity(16);
+        table.store(entry(3, 4));
+        table.store(entry(19, 2));
+        assert_eq!(table.probe(3.into()).unwrap().depth, 4);
+        table.store(entry(19, 4));
+        assert_eq!(table.probe(19.into()).unwrap().depth, 4);
+    }
+
+    #[test]
+    fn older_entries_are_replaced() {
+        let table = TranspositionTable::with_capacity(16);
+        table.store(entry(3, 6));
+        table.new_search();
+        table.store(entry(19, 1));
+        assert_eq!(table.probe(3.into()), None);
+        assert_eq!(table.probe(19.into()).unwrap().age, 1);
+    }
+
+    #[test]
+    fn entry_round_trip() {
+        let table = TranspositionTable::with_capacity(16);
+        let entry = TranspositionEntry {
+            hash: 5.into(),
+            depth: -1,
+            bound: Bound::Upper,
+            best_ply: Some(PlyKey {
+                from: 255.into(),
+                to: 0.into(),
+                promoting: Some(PieceType::Knight),
+            }),
+            score: -(MATE_SCORE - 3),
+            age: 0,
+        };
+        table.store(entry);
+        assert_eq!(table.probe(5.into()), Some(entry));
+    }
+
+    #[test]
+    fn clear_and_resize() {
+        let mut table = TranspositionTable::default();
+        assert_eq!(table.capacity(), TranspositionTable::DEFAULT_CAPACITY);
+        table.store(entry(3, 1));
+        table.clear();
+        assert!(table.is_empty());
+
+        table.store(entry(3, 1));
+        table.resize(8);
+        assert_eq!(table.capacity(), 8);
+        assert_eq!(table.probe(3.into()), None);
+    }
+
+    #[test]
+    fn mate_scores_relative_to_node() {
+        let mate_at_root = MATE_SCORE - 5;
+        let stored = score_to_table(mate_at_root, 2);
+        assert_eq!(stored, MATE_SCORE - 3);
+        assert_eq!(score_from_table(stored, 4), MATE_SCORE - 7);
+        assert_eq!(
+            score_from_table(score_to_table(-mate_at_root, 2), 2),
+            -mate_at_root
+        );
+        assert_eq!(score_to_table(150, 3), 150);
+    }
+}
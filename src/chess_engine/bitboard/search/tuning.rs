@@ -0,0 +1,354 @@
+//! Texel tuning of the `Weights`. Every position of a labelled set is scored by a quiescence
+//! search, a sigmoid maps the score to an expected game result, and each weight is nudged for as
+//! long as the mean squared error between expected and actual results keeps dropping.
+
+use std::{error::Error, fmt::Display, sync::Arc};
+
+use crate::chess_engine::{bitboard::FenError, pieces::PieceColor};
+
+use super::{
+    Bitboards, ClassicalEvaluator, Evaluator, SEARCH_STACK_SIZE, SearchLimits, SearchMeta,
+    TranspositionTable, Weights,
+};
+
+/// Slots of the transposition table of every tuning thread, cleared before each position so no
+/// score of previous weights is reused
+const TUNING_TABLE_SIZE: usize = 1 << 10;
+/// Scores are divided by this before the scaling of the sigmoid is applied
+const SIGMOID_DIVISOR: f64 = 400.0;
+/// Scaling of the sigmoid the fit starts from, with a step of half its value
+const INITIAL_SCALING: f64 = 1.0;
+/// Times the step of the scaling fit is halved
+const SCALING_FIT_ROUNDS: usize = 16;
+/// Weights left out of tuning. Both sides always have one king, so its material cancels out of
+/// the evaluation and only shifts exchange evaluation
+const UNTUNED_WEIGHTS: [&str; 1] = ["king"];
+
+/// Position of a played game with the result of that game
+#[derive(Debug, Clone)]
+pub struct LabelledPosition {
+    pub boards: Bitboards,
+    pub side_to_move: PieceColor,
+    /// Result for white, 1 for a win, 0.5 for a draw and 0 for a loss
+    pub result: f64,
+}
+
+impl LabelledPosition {
+    /// Reads `<fen> <result>`, the result given as `1-0`, `1/2-1/2`, `0-1` or a number between 0
+    /// and 1. Quotes, brackets and a trailing `;` around the result are ignored, as is an EPD
+    /// `c9` opcode in front of it
+    fn parse(line_number: usize, line: &str) -> Result<Self, LabelError> {
+        let line = line.trim().trim_end_matches(';');
+        let invalid_result = |result: &str| LabelError::InvalidResult {
+            line: line_number,
+            result: result.to_string(),
+        };
+        let (fen, result) = line
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(|| invalid_result(""))?;
+        let result = match result.trim_matches(['"', '[', ']']) {
+            "1-0" => 1.0,
+            "1/2-1/2" => 0.5,
+            "0-1" => 0.0,
+            number => number
+                .parse::<f64>()
+                .ok()
+                .filter(|result| (0.0..=1.0).contains(result))
+                .ok_or_else(|| invalid_result(result))?,
+        };
+
+        let fen = fen.trim_end();
+        let fen = fen.strip_suffix(" c9").unwrap_or(fen);
+        let (boards, info) = Bitboards::from_fen(fen).map_err(|error| LabelError::Fen {
+            line: line_number,
+            error,
+        })?;
+        Ok(Self {
+            boards,
+            side_to_move: info.side_to_move,
+            result,
+        })
+    }
+
+    /// Quiescence score from the view of white, searched with a cleared `table`
+    fn quiescence_score(
+        &mut self,
+        evaluator: &ClassicalEvaluator,
+        table: &Arc<TranspositionTable>,
+    ) -> i32 {
+        table.clear();
+        self.boards.transposition_table = table.clone();
+        let mut evaluator = evaluator.clone();
+        evaluator.reset(&self.boards);
+        let mut meta =
+            SearchMeta::with_limits(evaluator, self.side_to_move, &SearchLimits::default());
+        self.boards.quiescence_search(&mut meta, i32::MIN, i32::MAX)
+            * self.side_to_move.score_sign()
+    }
+}
+
+/// Errors raised while reading a labelled position set, lines are counted from 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum LabelError {
+    Fen { line: usize, error: FenError },
+    InvalidResult { line: usize, result: String },
+}
+
+impl Display for LabelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fen { line, error } => write!(f, "line {}: invalid position: {}", line, error),
+            Self::InvalidResult { line, result } => {
+                write!(f, "line {}: invalid game result '{}'", line, result)
+            }
+        }
+    }
+}
+
+impl Error for LabelError {}
+
+/// Outcome of a completed tuning pass over the tuned weights
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningReport {
+    pub pass: usize,
+    /// Mean squared error of the weights after the pass
+    pub error: f64,
+    /// Weights changed during the pass
+    pub changed: usize,
+    pub weights: Weights,
+}
+
+/// Expected result for white by a score from the view of white
+fn expected_result(score: i32, scaling: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-scaling * score as f64 / SIGMOID_DIVISOR))
+}
+
+pub struct Tuner {
+    positions: Vec<LabelledPosition>,
+    threads: usize,
+}
+
+impl Tuner {
+    /// Positions of the same board size share their caches, which only depend on the position,
+    /// so a large set doesn't allocate a set of caches per position
+    pub fn new(mut positions: Vec<LabelledPosition>, threads: usize) -> Self {
+        let mut cache_owners: Vec<usize> = vec![];
+        for i in 0..positions.len() {
+            let owner = cache_owners
+                .iter()
+                .copied()
+                .find(|owner| positions[*owner].boards.limits == positions[i].boards.limits);
+            match owner {
+                Some(owner) => {
+                    positions[i].boards.en_prise_table =
+                        positions[owner].boards.en_prise_table.clone();
+                    positions[i].boards.pawn_table = positions[owner].boards.pawn_table.clone();
+                }
+                None => cache_owners.push(i),
+            }
+        }
+        Self {
+            positions,
+            threads: threads.max(1),
+        }
+    }
+
+    /// Reads a position per line, see `LabelledPosition::parse`. Empty lines are skipped
+    pub fn from_labelled(input: &str, threads: usize) -> Result<Self, LabelError> {
+        let positions = input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| LabelledPosition::parse(i + 1, line))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(positions, threads))
+    }
+
+    pub fn positions(&self) -> &[LabelledPosition] {
+        &self.positions
+    }
+
+    /// Quiescence scores of all positions from the view of white, searched in parallel
+    pub fn scores(&mut self, weights: &Weights) -> Vec<i32> {
+        let evaluator = ClassicalEvaluator::from(weights.clone());
+        let chunk_size = self.positions.len().div_ceil(self.threads).max(1);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = self
+                .positions
+                .chunks_mut(chunk_size)
+                .map(|positions| {
+                    let evaluator = &evaluator;
+                    std::thread::Builder::new()
+                        .stack_size(SEARCH_STACK_SIZE)
+                        .spawn_scoped(scope, move || {
+                            let table =
+                                Arc::new(TranspositionTable::with_capacity(TUNING_TABLE_SIZE));
+                            positions
+                                .iter_mut()
+                                .map(|position| position.quiescence_score(evaluator, &table))
+                                .collect::<Vec<_>>()
+                        })
+                        .expect("failed to spawn tuning thread")
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("tuning thread panicked"))
+                .collect()
+        })
+    }
+
+    /// Mean squared error between the results and the results expected by `scores`
+    fn scores_error(&self, scores: &[i32], scaling: f64) -> f64 {
+        let sum: f64 = self
+            .positions
+            .iter()
+            .zip(scores)
+            .map(|(position, score)| (position.result - expected_result(*score, scaling)).powi(2))
+            .sum();
+        sum / self.positions.len().max(1) as f64
+    }
+
+    /// Mean squared error of the results expected with `weights`
+    pub fn error(&mut self, weights: &Weights, scaling: f64) -> f64 {
+        let scores = self.scores(weights);
+        self.scores_error(&scores, scaling)
+    }
+
+    /// Scaling of the sigmoid which best predicts the results by the scores of `weights`
+    pub fn fit_scaling(&mut self, weights: &Weights) -> f64 {
+        let scores = self.scores(weights);
+        let mut scaling = INITIAL_SCALING;
+        let mut best = self.scores_error(&scores, scaling);
+        let mut step = INITIAL_SCALING / 2.0;
+        for _ in 0..SCALING_FIT_ROUNDS {
+            let improved = [scaling + step, scaling - step]
+                .into_iter()
+                .filter(|candidate| *candidate > 0.0)
+                .map(|candidate| (candidate, self.scores_error(&scores, candidate)))
+                .find(|(_, error)| *error < best);
+            match improved {
+                Some((candidate, error)) => {
+                    scaling = candidate;
+                    best = error;
+                }
+                None => step /= 2.0,
+            }
+        }
+        scaling
+    }
+
+    /// Tunes every weight of `Weights::NAMES` but the king value by local search, starting from
+    /// `weights`. A pass moves each weight by one in the direction lowering the error, if any.
+    /// Stops after `passes` or once a pass changes no weight, `report` is called after every pass
+    pub fn tune(
+        &mut self,
+        mut weights: Weights,
+        passes: usize,
+        report: &mut dyn FnMut(&TuningReport),
+    ) -> Weights {
+        let scaling = self.fit_scaling(&weights);
+        let mut best = self.error(&weights, scaling);
+        for pass in 1..=passes {
+            let mut changed = 0;
+            for name in Weights::NAMES
+                .into_iter()
+                .filter(|name| !UNTUNED_WEIGHTS.contains(name))
+            {
+                for step in [1, -1] {
+                    let mut candidate = weights.clone();
+                    *candidate.get_mut(name).unwrap() += step;
+                    let error = self.error(&candidate, scaling);
+                    if error < best {
+                        best = error;
+                        weights = candidate;
+                        changed += 1;
+                        break;
+                    }
+                }
+            }
+
+            report(&TuningReport {
+                pass,
+                error: best,
+                changed,
+                weights: weights.clone(),
+            });
+            if changed == 0 {
+                break;
+            }
+        }
+        weights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_results() {
+        let tuner = Tuner::from_labelled(
+            r#"
+            4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 1-0
+            4k3/8/8/8/8/8/4P3/4K3 b - - c9 "1/2-1/2";
+            4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 [0.0]
+            "#,
+            1,
+        )
+        .unwrap();
+        let results: Vec<f64> = tuner.positions().iter().map(|p| p.result).collect();
+        assert_eq!(results, vec![1.0, 0.5, 0.0]);
+        assert_eq!(tuner.positions()[1].side_to_move, PieceColor::Black);
+
+        assert_eq!(
+            Tuner::from_labelled("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 2-0", 1).err(),
+            Some(LabelError::InvalidResult {
+                line: 1,
+                result: "2-0".to_string()
+            })
+        );
+        assert!(matches!(
+            Tuner::from_labelled("\n4k3/8/8/8/8/8/4X3/4K3 w - - 0 1 1-0", 1),
+            Err(LabelError::Fen { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn scores_from_view_of_white() {
+        // black to move wins the hanging queen
+        let mut tuner = Tuner::from_labelled("4k3/8/8/8/8/Q2q4/8/4K3 b - - 0 1 0-1", 1).unwrap();
+        let weights = Weights::default();
+        let scores = tuner.scores(&weights);
+        assert!(scores[0] < -weights.queen / 2);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn tuning_lowers_error() {
+        // white wins every game with the extra knight, so the knight is undervalued at zero
+        let input = [
+            "4k3/8/8/8/8/8/8/1N2K3 w - - 0 1 1-0",
+            "4k3/8/8/8/3N4/8/8/4K3 b - - 0 1 1-0",
+            "3k4/8/8/8/8/8/8/N3K3 w - - 0 1 1-0",
+            "8/4k3/8/8/8/5N2/8/4K3 b - - 0 1 1-0",
+            "4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1 1/2-1/2",
+            "4k3/p7/8/8/8/8/P7/4K3 b - - 0 1 1/2-1/2",
+        ]
+        .join("\n");
+        let mut tuner = Tuner::from_labelled(&input, 2).unwrap();
+        let weights = Weights {
+            knight: 0,
+            ..Default::default()
+        };
+        let scaling = tuner.fit_scaling(&weights);
+        let before = tuner.error(&weights, scaling);
+
+        let mut reports = vec![];
+        let tuned = tuner.tune(weights, 2, &mut |report| reports.push(report.clone()));
+        assert_eq!(reports.len(), 2);
+        assert!(reports[1].error < before);
+        assert!(tuned.knight > 0);
+        assert_eq!(tuned.king, Weights::default().king);
+    }
+}
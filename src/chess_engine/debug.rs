@@ -1,14 +1,14 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use bevy::prelude::*;
 
 use super::{
-    bitboard::{Ply, Weights},
-    game::Game,
+    bitboard::{ClassicalEvaluator, SearchLimits, Weights},
+    game::{Game, search_comment},
 };
 
-#[derive(Resource, Debug, Clone, Copy, Default)]
-struct LastPly(Option<Ply>);
+/// Upper bound of a single search, as it blocks the `Update` schedule
+const SEARCH_TIME: Duration = Duration::from_millis(500);
 
 #[derive(Resource, Debug, Clone, Default, Deref)]
 struct NextBoard(Option<(String, String)>);
@@ -17,9 +17,8 @@ pub struct ChessDebugPlugin;
 impl Plugin for ChessDebugPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup_debug)
-            .add_systems(Update, (find_next_ply, print_new_board))
+            .add_systems(Update, (find_next_ply, print_new_board, adjust_threads))
             .init_resource::<DebugFlags>()
-            .init_resource::<LastPly>()
             .init_resource::<NextBoard>();
     }
 }
@@ -33,12 +32,15 @@ struct DebugTextInfo;
 struct DebugFlags {
     running: bool,
     waiting_to_print: bool,
+    /// Searching threads, adjusted with the arrow keys
+    threads: usize,
 }
 impl Default for DebugFlags {
     fn default() -> Self {
         Self {
             running: true,
             waiting_to_print: true,
+            threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
         }
     }
 }
@@ -92,19 +94,27 @@ fn print_new_board(
         debug_flags.waiting_to_print = true;
     }
 
-    if debug_flags.waiting_to_print {
-        if let NextBoard(Some((board, info))) = next_board.clone() {
-            *next_board = NextBoard(None);
-            board_text_query.single_mut().0 = board;
-            info_text_query.single_mut().0 = info;
-            debug_flags.waiting_to_print = false;
-        }
+    if debug_flags.waiting_to_print
+        && let NextBoard(Some((board, info))) = next_board.clone()
+    {
+        *next_board = NextBoard(None);
+        board_text_query.single_mut().0 = board;
+        info_text_query.single_mut().0 = info;
+        debug_flags.waiting_to_print = false;
+    }
+}
+
+fn adjust_threads(mut debug_flags: ResMut<DebugFlags>, input: Res<ButtonInput<KeyCode>>) {
+    if input.just_pressed(KeyCode::ArrowUp) {
+        debug_flags.threads += 1;
+    }
+    if input.just_pressed(KeyCode::ArrowDown) {
+        debug_flags.threads = debug_flags.threads.saturating_sub(1).max(1);
     }
 }
 
 fn find_next_ply(
     mut game: ResMut<Game>,
-    mut last_ply: ResMut<LastPly>,
     mut debug_flags: ResMut<DebugFlags>,
     mut next_board: ResMut<NextBoard>,
 ) {
@@ -122,36 +132,50 @@ fn find_next_ply(
         //         .push_str(&format!("\nTime: {}", work_done.as_millis()));
 
         // Bitboard impl
-        let weights: Weights = Weights {
-            king: 4000,
-            queen: 180,
-            rook: 100,
-            bishop: 60,
-            knight: 60,
-            pawn: 20,
-            isolated_pawn: -5,
-            movement: 1,
+        let weights = Weights::default();
+        let side_to_move = game.side_to_move();
+        let limits = SearchLimits {
+            depth: Some(3),
+            movetime: Some(SEARCH_TIME),
+            threads: debug_flags.threads,
+            ..Default::default()
+        };
+        let result = match game.result() {
+            None => {
+                let (score, pv, nodes) = game.boards.search_with_limits(
+                    side_to_move,
+                    &limits,
+                    ClassicalEvaluator::from(weights.clone()),
+                    &mut |_| {},
+                );
+                (score, pv.first().copied(), nodes)
+            }
+            Some(_) => (0, None, 0),
         };
-        let result = game.boards.search_next_ply(last_ply.0, 3, weights);
         if let Some(ply) = result.1 {
-            game.boards.make_ply(&ply);
-            last_ply.0 = Some(ply);
+            let san = game.boards.ply_to_san(&ply);
+            game.play(ply).expect("search returned an illegal ply");
+            game.annotate(search_comment(result.0, result.2, side_to_move, &weights));
             let work_done = Instant::now().duration_since(start);
 
             *next_board = NextBoard(Some((
                 game.boards.to_string(),
                 format!(
-                    "{}\nTime:\n{}\n\n Nodes visited:\n{}",
-                    ply,
+                    "{}\nTime:\n{}\n\n Nodes visited:\n{}\n\n Threads:\n{}",
+                    san,
                     work_done.as_millis(),
-                    result.2
+                    result.2,
+                    debug_flags.threads
                 ),
             )));
 
         ////////////////////////////////////////////////////////////////////
         } else {
             let board = game.to_string();
-            let info = format!("\n{:?} lost!", 0); // TODO: update game state
+            let info = match game.result() {
+                Some(result) => format!("\n{}", result),
+                None => "\nNo ply found".to_string(),
+            };
             *next_board = NextBoard(Some((board, info)));
             debug_flags.running = false;
         }
@@ -1,10 +1,66 @@
-use super::bitboard::Bitboards;
+use super::{
+    bitboard::{Bitboards, FenError, Ply, PositionInfo},
+    pieces::{PieceColor, PieceType},
+};
 use bevy::prelude::*;
 use std::fmt::Display;
 
+mod pgn;
+pub use pgn::{PgnError, search_comment};
+
+/// Plys without capture or pawn move until the game is drawn
+const FIFTY_MOVE_RULE_PLYS: u32 = 100;
+
+/// Outcome of a finished game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    Checkmate { winner: PieceColor },
+    Stalemate,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+impl Display for GameResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameResult::Checkmate { winner } => write!(f, "{:?} wins by checkmate", winner),
+            GameResult::Stalemate => write!(f, "Draw by stalemate"),
+            GameResult::ThreefoldRepetition => write!(f, "Draw by threefold repetition"),
+            GameResult::FiftyMoveRule => write!(f, "Draw by fifty-move rule"),
+            GameResult::InsufficientMaterial => write!(f, "Draw by insufficient material"),
+        }
+    }
+}
+
+/// Reasons `Game::play` refuses a ply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    /// The ply is not among the legal plys of the side to move
+    IllegalPly(Ply),
+    /// The game already ended
+    GameOver(GameResult),
+}
+impl Display for GameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameError::IllegalPly(ply) => write!(f, "illegal ply: {}", ply),
+            GameError::GameOver(result) => write!(f, "game is over: {}", result),
+        }
+    }
+}
+impl std::error::Error for GameError {}
+
 #[derive(Resource, Debug, Clone)]
 pub struct Game {
     pub boards: Bitboards,
+    side_to_move: PieceColor,
+    /// Plys since the last capture or pawn move
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    /// Played plys, together with the halfmove clock before each of them and an optional comment
+    history: Vec<(Ply, u32, Option<String>)>,
+    /// Position the game started from, in FEN
+    start_position: String,
 }
 impl Default for Game {
     fn default() -> Self {
@@ -31,11 +87,166 @@ impl Display for Game {
 
 impl Game {
     pub fn new_from_str(input: &str) -> Self {
+        Self::with_position(Bitboards::new_from_str(input), PositionInfo::default())
+    }
+
+    pub fn from_fen(input: &str) -> Result<Self, FenError> {
+        let (boards, info) = Bitboards::from_fen(input)?;
+        Ok(Self::with_position(boards, info))
+    }
+
+    pub fn to_fen(&self) -> String {
+        self.boards.to_fen(&self.position_info())
+    }
+
+    fn with_position(boards: Bitboards, info: PositionInfo) -> Self {
         Self {
-            boards: Bitboards::new_from_str(input),
+            start_position: boards.to_fen(&info),
+            boards,
+            side_to_move: info.side_to_move,
+            halfmove_clock: info.halfmove_clock,
+            fullmove_number: info.fullmove_number,
+            history: vec![],
+        }
+    }
+
+    pub fn position_info(&self) -> PositionInfo {
+        PositionInfo {
+            side_to_move: self.side_to_move,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
         }
     }
 
+    pub fn side_to_move(&self) -> PieceColor {
+        self.side_to_move
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    /// Plys played so far, oldest first
+    pub fn history(&self) -> impl Iterator<Item = &Ply> {
+        self.history.iter().map(|(ply, _, _)| ply)
+    }
+
+    pub fn last_ply(&self) -> Option<Ply> {
+        self.history.last().map(|(ply, _, _)| *ply)
+    }
+
+    /// Attaches a comment to the last played ply, replacing any previous one.
+    /// Does nothing if no ply has been played yet.
+    pub fn annotate(&mut self, comment: impl Into<String>) {
+        if let Some((_, _, last_comment)) = self.history.last_mut() {
+            *last_comment = Some(comment.into());
+        }
+    }
+
+    /// Comments of the played plys, oldest first
+    pub fn comments(&self) -> impl Iterator<Item = Option<&str>> {
+        self.history
+            .iter()
+            .map(|(_, _, comment)| comment.as_deref())
+    }
+
+    /// All legal plys of the side to move
+    pub fn legal_moves(&mut self) -> Vec<Ply> {
+        self.boards.all_legal_plys_by_color(self.side_to_move)
+    }
+
+    /// Plays `ply` for the side to move, if it is legal and the game is ongoing
+    pub fn play(&mut self, ply: Ply) -> Result<(), GameError> {
+        if let Some(result) = self.result() {
+            return Err(GameError::GameOver(result));
+        }
+        // `pv_move` and `order_score` only affect ordering, so ignore them when matching
+        let Some(ply) = self.legal_moves().into_iter().find(|legal| {
+            Ply {
+                pv_move: legal.pv_move,
+                order_score: legal.order_score,
+                ..ply
+            } == *legal
+        }) else {
+            return Err(GameError::IllegalPly(ply));
+        };
+
+        self.boards.make_ply(&ply);
+        self.history.push((ply, self.halfmove_clock, None));
+
+        if ply.capturing.is_some() || ply.moving_piece.0 == PieceType::Pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.side_to_move == PieceColor::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = self.side_to_move.next();
+        Ok(())
+    }
+
+    /// Takes back the last played ply, returning it
+    pub fn undo(&mut self) -> Option<Ply> {
+        let (ply, halfmove_clock, _) = self.history.pop()?;
+        self.boards.unmake_ply(&ply);
+
+        self.halfmove_clock = halfmove_clock;
+        self.side_to_move = self.side_to_move.next();
+        if self.side_to_move == PieceColor::Black {
+            self.fullmove_number -= 1;
+        }
+        Some(ply)
+    }
+
+    /// Result of the game, `None` while it is ongoing
+    pub fn result(&mut self) -> Option<GameResult> {
+        if self.legal_moves().is_empty() {
+            return Some(if self.boards.in_check(self.side_to_move) {
+                GameResult::Checkmate {
+                    winner: self.side_to_move.next(),
+                }
+            } else {
+                GameResult::Stalemate
+            });
+        }
+
+        if self.boards.repetition_count() >= 3 {
+            Some(GameResult::ThreefoldRepetition)
+        } else if self.halfmove_clock >= FIFTY_MOVE_RULE_PLYS {
+            Some(GameResult::FiftyMoveRule)
+        } else if self.insufficient_material() {
+            Some(GameResult::InsufficientMaterial)
+        } else {
+            None
+        }
+    }
+
+    /// Neither side can mate: bare kings plus at most one minor piece,
+    /// or only bishops which all stand on tiles of the same color
+    fn insufficient_material(&self) -> bool {
+        let mut knights = 0;
+        let mut bishops = 0;
+        let mut bishop_tile_colors = [false; 2];
+        for (piece, idx) in self.boards.key_value_pieces_iter() {
+            match piece.0 {
+                PieceType::King => {}
+                PieceType::Queen | PieceType::Rook | PieceType::Pawn => return false,
+                PieceType::Knight => knights += 1,
+                PieceType::Bishop => {
+                    bishops += 1;
+                    bishop_tile_colors[((*idx / 16 + *idx % 16) % 2) as usize] = true;
+                }
+            }
+        }
+        knights + bishops <= 1
+            || (knights == 0 && !(bishop_tile_colors[0] && bishop_tile_colors[1]))
+    }
+
     // /// Returns the legal moves for a piece at a given position
     // fn get_moves_for_pos(&self, pos: Pos) -> Option<Vec<LegacyPly>> {
     //     let piece = self[pos];
@@ -460,6 +671,129 @@ impl Game {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    /// Plays the legal ply between two named squares
+    fn play_squares(game: &mut Game, from: &str, to: &str) -> Result<(), GameError> {
+        let from = game.boards.parse_square(from).unwrap();
+        let to = game.boards.parse_square(to).unwrap();
+        let ply = game
+            .legal_moves()
+            .into_iter()
+            .find(|ply| ply.from == from && ply.to == to)
+            .unwrap();
+        game.play(ply)
+    }
+
+    #[test]
+    fn play_tracks_side_to_move_and_counters() {
+        let mut game = Game::default();
+        assert_eq!(game.side_to_move(), PieceColor::White);
+
+        play_squares(&mut game, "g1", "f3").unwrap();
+        assert_eq!(game.side_to_move(), PieceColor::Black);
+        assert_eq!(game.halfmove_clock(), 1);
+        assert_eq!(game.fullmove_number(), 1);
+
+        play_squares(&mut game, "e7", "e5").unwrap();
+        assert_eq!(game.side_to_move(), PieceColor::White);
+        assert_eq!(game.halfmove_clock(), 0);
+        assert_eq!(game.fullmove_number(), 2);
+        assert_eq!(game.history().count(), 2);
+    }
+
+    #[test]
+    fn undo_restores_position() {
+        let mut game = Game::default();
+        let fen = game.to_fen();
+
+        play_squares(&mut game, "e2", "e4").unwrap();
+        play_squares(&mut game, "b8", "c6").unwrap();
+        assert_ne!(game.to_fen(), fen);
+
+        assert!(game.undo().is_some());
+        assert!(game.undo().is_some());
+        assert!(game.undo().is_none());
+        assert_eq!(game.to_fen(), fen);
+        assert_eq!(game.side_to_move(), PieceColor::White);
+    }
+
+    #[test]
+    fn play_rejects_illegal_ply() {
+        let mut game = Game::default();
+        let black_ply = game
+            .boards
+            .all_legal_plys_by_color::<Vec<Ply>>(PieceColor::Black)[0];
+        assert_eq!(game.play(black_ply), Err(GameError::IllegalPly(black_ply)));
+        assert_eq!(game.history().count(), 0);
+    }
+
+    #[test]
+    fn result_checkmate() {
+        let mut game = Game::new_from_str(
+            r#"
+            kR0
+            0R0
+            0r0
+            "#,
+        );
+        assert_eq!(
+            game.result(),
+            Some(GameResult::Checkmate {
+                winner: PieceColor::Black
+            })
+        );
+        let ply = Ply::default();
+        assert!(matches!(game.play(ply), Err(GameError::GameOver(_))));
+    }
+
+    #[test]
+    fn result_stalemate() {
+        let mut game = Game::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game.result(), Some(GameResult::Stalemate));
+    }
+
+    #[test]
+    fn result_threefold_repetition() {
+        let mut game = Game::default();
+        for _ in 0..2 {
+            assert_eq!(game.result(), None);
+            play_squares(&mut game, "g1", "f3").unwrap();
+            play_squares(&mut game, "g8", "f6").unwrap();
+            play_squares(&mut game, "f3", "g1").unwrap();
+            play_squares(&mut game, "f6", "g8").unwrap();
+        }
+        assert_eq!(game.result(), Some(GameResult::ThreefoldRepetition));
+
+        game.undo();
+        assert_eq!(game.result(), None);
+    }
+
+    #[test]
+    fn result_fifty_move_rule() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 60").unwrap();
+        assert_eq!(game.result(), None);
+        play_squares(&mut game, "a1", "a2").unwrap();
+        assert_eq!(game.result(), Some(GameResult::FiftyMoveRule));
+    }
+
+    #[test]
+    fn result_insufficient_material() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1").unwrap();
+        assert_eq!(game.result(), Some(GameResult::InsufficientMaterial));
+
+        // bishops on tiles of the same color
+        let mut game = Game::from_fen("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1").unwrap();
+        assert_eq!(game.result(), Some(GameResult::InsufficientMaterial));
+
+        // bishops on tiles of different colors
+        let mut game = Game::from_fen("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1").unwrap();
+        assert_eq!(game.result(), None);
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.result(), None);
+    }
+
     // use crate::chess_engine::moves::MoveTo;
 
     // use super::*;
@@ -0,0 +1,441 @@
+//! PGN import and export of `Game`s.
+//! Positions which don't fit the standard 8x8 board are carried in a custom `BoardLayout` tag
+//! holding the project's extended FEN, standard boards use the regular `SetUp`/`FEN` tags.
+
+use ethnum::u256;
+
+use crate::chess_engine::{
+    bitboard::{Bitboards, FenError, STARTING_FEN, Weights, mate_distance},
+    pieces::PieceColor,
+};
+
+use super::{Game, GameResult};
+
+/// Seven tag roster, always exported in this order
+const ROSTER_TAGS: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+/// Custom tag holding the start position of games on non-standard boards
+const LAYOUT_TAG: &str = "BoardLayout";
+const MAX_LINE_LENGTH: usize = 80;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnError {
+    /// A tag pair line which is not of the form `[Name "Value"]`
+    InvalidTag(String),
+    Fen(FenError),
+    /// First move which could not be played, with its 1-based ply number
+    IllegalMove {
+        ply_number: usize,
+        san: String,
+    },
+}
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::InvalidTag(line) => write!(f, "invalid tag pair: {}", line),
+            PgnError::Fen(err) => write!(f, "invalid start position: {}", err),
+            PgnError::IllegalMove { ply_number, san } => {
+                write!(f, "illegal move {} at ply {}", san, ply_number)
+            }
+        }
+    }
+}
+impl std::error::Error for PgnError {}
+impl From<FenError> for PgnError {
+    fn from(value: FenError) -> Self {
+        PgnError::Fen(value)
+    }
+}
+
+impl GameResult {
+    /// Result token as written in PGN
+    pub fn as_pgn_result(&self) -> &'static str {
+        match self {
+            GameResult::Checkmate {
+                winner: PieceColor::White,
+            } => "1-0",
+            GameResult::Checkmate {
+                winner: PieceColor::Black,
+            } => "0-1",
+            _ => "1/2-1/2",
+        }
+    }
+}
+
+/// Comment describing a search result, with the evaluation from White's view in pawns.
+/// `score` is from the view of `searched_by`, as returned by `search_next_ply`.
+pub fn search_comment(
+    score: i32,
+    nodes: u64,
+    searched_by: PieceColor,
+    weights: &Weights,
+) -> String {
+    let eval = match mate_distance(score) {
+        Some(plys) => format!("#{}", searched_by.score_sign() * (plys + plys.signum()) / 2),
+        None => format!(
+            "{:.2}",
+            (score * searched_by.score_sign()) as f32 / weights.pawn.max(1) as f32
+        ),
+    };
+    format!("[%eval {}] [%nodes {}]", eval, nodes)
+}
+
+/// Whether the active area is exactly the top left 8x8 tiles
+fn is_standard_board(boards: &Bitboards) -> bool {
+    let standard = (0..8).fold(u256::ZERO, |mask, row| {
+        mask | u256::from(0xFFu8) << (row * 16)
+    });
+    *boards.limits() == standard
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Parses a tag pair line without its leading `[`
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.strip_suffix(']')?.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let value = value.replace("\\\"", "\"").replace("\\\\", "\\");
+    Some((name.to_string(), value))
+}
+
+/// Joins tokens with spaces, wrapping lines before they exceed `MAX_LINE_LENGTH`
+fn wrap(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+        text.push_str(token);
+        line_length += token.len();
+    }
+    text
+}
+
+impl Game {
+    /// Exports the game as PGN. `tags` override or extend the seven tag roster,
+    /// whose entries default to `?` and the current result.
+    pub fn to_pgn(&self, tags: &[(&str, &str)]) -> String {
+        let mut replay = Game::from_fen(&self.start_position).expect("start position is valid");
+
+        let mut movetext = vec![];
+        let mut needs_number = true;
+        for (ply, _, comment) in self.history.iter() {
+            if replay.side_to_move == PieceColor::White {
+                movetext.push(format!("{}.", replay.fullmove_number));
+            } else if needs_number {
+                movetext.push(format!("{}...", replay.fullmove_number));
+            }
+            movetext.push(replay.boards.ply_to_san(ply));
+            replay.play(*ply).expect("history only contains legal plys");
+
+            needs_number = comment.is_some();
+            if let Some(comment) = comment {
+                movetext.push(format!("{{{}}}", comment));
+            }
+        }
+        let result = replay
+            .result()
+            .map(|result| result.as_pgn_result())
+            .unwrap_or("*");
+        movetext.push(result.to_string());
+
+        // Tag pairs
+        let mut all_tags: Vec<(&str, &str)> = ROSTER_TAGS
+            .iter()
+            .map(|&name| (name, if name == "Result" { result } else { "?" }))
+            .collect();
+        if self.start_position != STARTING_FEN {
+            if is_standard_board(&self.boards) {
+                all_tags.push(("SetUp", "1"));
+                all_tags.push(("FEN", &self.start_position));
+            } else {
+                all_tags.push((LAYOUT_TAG, &self.start_position));
+            }
+        }
+        for &(name, value) in tags {
+            match all_tags.iter_mut().find(|(existing, _)| *existing == name) {
+                Some(tag) => tag.1 = value,
+                None => all_tags.push((name, value)),
+            }
+        }
+
+        let mut pgn: String = all_tags
+            .iter()
+            .map(|(name, value)| format!("[{} \"{}\"]\n", name, escape(value)))
+            .collect();
+        pgn.push('\n');
+        pgn.push_str(&wrap(&movetext));
+        pgn.push('\n');
+        pgn
+    }
+
+    /// Imports the first game of a PGN text, replaying its moves through legal move generation.
+    /// Returns the game and all tag pairs; comments are attached to the preceding ply,
+    /// variations and numeric annotations are skipped.
+    pub fn from_pgn(input: &str) -> Result<(Self, Vec<(String, String)>), PgnError> {
+        let mut tags = vec![];
+        let mut movetext = String::new();
+        for line in input.lines() {
+            let line = line.trim();
+            if line.starts_with('%') {
+                // escaped line
+                continue;
+            }
+            if let Some(tag) = line.strip_prefix('[') {
+                if !movetext.trim().is_empty() {
+                    // start of the next game
+                    break;
+                }
+                tags.push(parse_tag(tag).ok_or_else(|| PgnError::InvalidTag(line.to_string()))?);
+            } else {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+
+        let start_position = tags
+            .iter()
+            .find(|(name, _)| name == LAYOUT_TAG)
+            .or_else(|| tags.iter().find(|(name, _)| name == "FEN"))
+            .map(|(_, value)| value.as_str())
+            .unwrap_or(STARTING_FEN);
+        let mut game = Game::from_fen(start_position)?;
+
+        let mut chars = movetext.chars();
+        let mut token = String::new();
+        while let Some(char) = chars.next() {
+            match char {
+                '{' => {
+                    game.play_pgn_token(&mut token)?;
+                    let comment: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    game.annotate(comment.trim());
+                }
+                ';' => {
+                    game.play_pgn_token(&mut token)?;
+                    let comment: String = chars.by_ref().take_while(|c| *c != '\n').collect();
+                    game.annotate(comment.trim());
+                }
+                '(' => {
+                    game.play_pgn_token(&mut token)?;
+                    let mut depth = 1;
+                    for c in chars.by_ref() {
+                        match c {
+                            '(' => depth += 1,
+                            ')' => depth -= 1,
+                            _ => {}
+                        }
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                }
+                c if c.is_whitespace() => {
+                    if game.play_pgn_token(&mut token)? {
+                        break;
+                    }
+                }
+                c => token.push(c),
+            }
+        }
+        game.play_pgn_token(&mut token)?;
+
+        Ok((game, tags))
+    }
+
+    /// Plays a single movetext token and clears it. Move numbers and annotations are skipped.
+    /// Returns `true` on the game termination marker.
+    fn play_pgn_token(&mut self, token: &mut String) -> Result<bool, PgnError> {
+        let mut san = token.as_str();
+        if matches!(san, "1-0" | "0-1" | "1/2-1/2" | "*") {
+            token.clear();
+            return Ok(true);
+        }
+        // move numbers, possibly directly followed by the move
+        if let Some((number, rest)) = san.split_once('.')
+            && number.chars().all(|c| c.is_ascii_digit())
+        {
+            san = rest.trim_start_matches('.');
+        }
+        if san.is_empty() || san.starts_with('$') {
+            token.clear();
+            return Ok(false);
+        }
+
+        let ply_number = self.history.len() + 1;
+        let illegal = || PgnError::IllegalMove {
+            ply_number,
+            san: san.to_string(),
+        };
+        let ply = self
+            .boards
+            .ply_from_san(self.side_to_move, san)
+            .ok_or_else(illegal)?;
+        self.play(ply).map_err(|_| illegal())?;
+
+        token.clear();
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays a list of SAN moves from the given game
+    fn play_san(game: &mut Game, moves: &[&str]) {
+        for san in moves {
+            let ply = game.boards.ply_from_san(game.side_to_move(), san).unwrap();
+            game.play(ply).unwrap();
+        }
+    }
+
+    #[test]
+    fn export_movetext_and_roster() {
+        let mut game = Game::default();
+        play_san(&mut game, &["e4", "e5", "Nf3"]);
+        let pgn = game.to_pgn(&[("White", "Alice"), ("Annotator", "engine")]);
+
+        assert_eq!(
+            pgn,
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"?\"]\n[Round \"?\"]\n[White \"Alice\"]\n\
+             [Black \"?\"]\n[Result \"*\"]\n[Annotator \"engine\"]\n\n1. e4 e5 2. Nf3 *\n"
+        );
+    }
+
+    #[test]
+    fn export_checkmate_result() {
+        let mut game = Game::default();
+        play_san(&mut game, &["f3", "e5", "g4", "Qh4#"]);
+        let pgn = game.to_pgn(&[]);
+
+        assert!(pgn.contains("[Result \"0-1\"]"));
+        assert!(pgn.ends_with("1. f3 e5 2. g4 Qh4# 0-1\n"));
+    }
+
+    #[test]
+    fn export_comments_and_black_to_move() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 12").unwrap();
+        play_san(&mut game, &["Kd7"]);
+        game.annotate("only move");
+        play_san(&mut game, &["e4", "Kc6"]);
+        let pgn = game.to_pgn(&[]);
+
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n"));
+        assert!(pgn.ends_with("12... Kd7 {only move} 13. e4 Kc6 *\n"));
+    }
+
+    #[test]
+    fn export_wraps_long_movetext() {
+        let mut game = Game::default();
+        play_san(
+            &mut game,
+            &[
+                "e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7", "Re1", "b5",
+                "Bb3", "d6", "c3", "O-O", "h3", "Nb8", "d4", "Nbd7",
+            ],
+        );
+        let pgn = game.to_pgn(&[]);
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(pgn.ends_with("10. d4 Nbd7 *\n"));
+    }
+
+    #[test]
+    fn import_round_trip() {
+        let mut game = Game::default();
+        play_san(
+            &mut game,
+            &["e4", "c5", "Nf3", "d6", "d4", "cxd4", "Nxd4", "Nf6"],
+        );
+        game.annotate("[%eval 0.35] [%nodes 1234]");
+        play_san(&mut game, &["Nc3", "a6"]);
+        let pgn = game.to_pgn(&[("Event", "Test \"quoted\"")]);
+
+        let (imported, tags) = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(imported.to_fen(), game.to_fen());
+        assert_eq!(imported.history().count(), 10);
+        assert_eq!(
+            imported.comments().nth(7),
+            Some(Some("[%eval 0.35] [%nodes 1234]"))
+        );
+        assert!(tags.contains(&("Event".to_string(), "Test \"quoted\"".to_string())));
+        assert_eq!(imported.to_pgn(&[("Event", "Test \"quoted\"")]), pgn);
+    }
+
+    #[test]
+    fn import_skips_variations_and_annotations() {
+        let pgn = r#"
+            [Event "Casual"]
+
+            1.e4 $1 e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3!? ; a comment until the line ends
+            2... Nc6 3. Bb5 a6 {Morphy defence} 1/2-1/2
+        "#;
+        let (game, tags) = Game::from_pgn(pgn).unwrap();
+        assert_eq!(tags, vec![("Event".to_string(), "Casual".to_string())]);
+        assert_eq!(game.history().count(), 6);
+        assert_eq!(
+            game.comments().collect::<Vec<_>>(),
+            vec![
+                None,
+                None,
+                Some("a comment until the line ends"),
+                None,
+                None,
+                Some("Morphy defence")
+            ]
+        );
+    }
+
+    #[test]
+    fn import_reports_first_illegal_move() {
+        let pgn = "1. e4 e5 2. Nf3 Nc6 3. Ke3 Nf6 *";
+        assert_eq!(
+            Game::from_pgn(pgn).err(),
+            Some(PgnError::IllegalMove {
+                ply_number: 5,
+                san: "Ke3".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn import_rejects_invalid_tag() {
+        assert_eq!(
+            Game::from_pgn("[Event Casual]\n\n1. e4 *").err(),
+            Some(PgnError::InvalidTag("[Event Casual]".to_string()))
+        );
+    }
+
+    #[test]
+    fn board_layout_tag_for_non_standard_boards() {
+        let layout = "r3k4r/pppppppppp/10/10/10/10/PPPPPPPPPP/R3K4R w KQkq - 0 1";
+        let mut game = Game::from_fen(layout).unwrap();
+        play_san(&mut game, &["e4", "e5", "O-O"]);
+        let pgn = game.to_pgn(&[]);
+
+        assert!(pgn.contains(&format!("[{} \"{}\"]", LAYOUT_TAG, layout)));
+        assert!(!pgn.contains("[FEN"));
+
+        let (imported, _) = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(imported.to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn search_comment_from_white_view() {
+        let weights = Weights::default();
+        assert_eq!(
+            search_comment(30, 1234, PieceColor::Black, &weights),
+            "[%eval -1.50] [%nodes 1234]"
+        );
+        let mate_in_two = crate::chess_engine::bitboard::MATE_SCORE - 3;
+        assert_eq!(
+            search_comment(mate_in_two, 10, PieceColor::Black, &weights),
+            "[%eval #-2] [%nodes 10]"
+        );
+    }
+}
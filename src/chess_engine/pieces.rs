@@ -49,6 +49,35 @@ impl Piece {
         }
     }
 
+    /// Character in FEN convention, where white pieces are uppercase
+    pub fn as_fen_char(&self) -> char {
+        let char = self.as_char();
+        if char.is_ascii_lowercase() {
+            char.to_ascii_uppercase()
+        } else {
+            char.to_ascii_lowercase()
+        }
+    }
+
+    /// Parses a character in FEN convention, where white pieces are uppercase
+    pub fn from_fen_char(value: char) -> Option<Self> {
+        match value {
+            'K' => Some(WHITE_KING),
+            'k' => Some(BLACK_KING),
+            'Q' => Some(WHITE_QUEEN),
+            'q' => Some(BLACK_QUEEN),
+            'R' => Some(WHITE_ROOK),
+            'r' => Some(BLACK_ROOK),
+            'B' => Some(WHITE_BISHOP),
+            'b' => Some(BLACK_BISHOP),
+            'N' => Some(WHITE_KNIGHT),
+            'n' => Some(BLACK_KNIGHT),
+            'P' => Some(WHITE_PAWN),
+            'p' => Some(BLACK_PAWN),
+            _ => None,
+        }
+    }
+
     /// Full iter through all possible PieceType, PieceColor combinations
     pub fn iter() -> impl Iterator<Item = Self> {
         PieceType::iter()
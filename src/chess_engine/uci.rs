@@ -0,0 +1,593 @@
+use std::{
+    sync::{Arc, mpsc::Sender},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use super::{
+    bitboard::{
+        Bitboards, ClassicalEvaluator, Clock, FenError, MAX_DEPTH, SEARCH_STACK_SIZE, STARTING_FEN,
+        SearchLimits, StopFlag, TranspositionTable, Weights, WeightsError, mate_distance,
+    },
+    game::Game,
+    pieces::PieceColor,
+};
+
+/// Option carrying the start position in the project's FEN format, for non 8x8 boards
+const START_POSITION_OPTION: &str = "StartPosition";
+/// Transposition table size in MiB
+const HASH_OPTION: &str = "Hash";
+const DEFAULT_HASH_MB: usize = 16;
+const MAX_HASH_MB: usize = 4096;
+/// Searching threads
+const THREADS_OPTION: &str = "Threads";
+const MAX_THREADS: usize = 256;
+/// Path of a weights file as written by the tuner, loading it replaces all weights
+const WEIGHTS_FILE_OPTION: &str = "WeightsFile";
+
+/// Errors raised while handling a UCI command
+#[derive(Debug, Clone, PartialEq)]
+pub enum UciError {
+    Fen(FenError),
+    /// Move in a `position` command which is not legal in the reached position
+    IllegalMove(String),
+    UnknownOption(String),
+    InvalidValue(String),
+    Weights(WeightsError),
+}
+impl std::fmt::Display for UciError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UciError::Fen(err) => write!(f, "invalid position: {}", err),
+            UciError::IllegalMove(ply) => write!(f, "illegal move: {}", ply),
+            UciError::UnknownOption(name) => write!(f, "unknown option: {}", name),
+            UciError::InvalidValue(value) => write!(f, "invalid value: {}", value),
+            UciError::Weights(err) => write!(f, "invalid weights: {}", err),
+        }
+    }
+}
+impl std::error::Error for UciError {}
+impl From<FenError> for UciError {
+    fn from(value: FenError) -> Self {
+        UciError::Fen(value)
+    }
+}
+impl From<WeightsError> for UciError {
+    fn from(value: WeightsError) -> Self {
+        UciError::Weights(value)
+    }
+}
+
+/// Limits of a `go` command, clock values are taken for `side_to_move`.
+/// Returns whether the search is infinite, in which case `bestmove` waits for `stop`
+fn parse_go<'a>(
+    mut args: impl Iterator<Item = &'a str>,
+    side_to_move: PieceColor,
+) -> (SearchLimits, bool) {
+    let mut limits = SearchLimits::default();
+    let mut clock = Clock::default();
+    let mut has_clock = false;
+    let mut infinite = false;
+
+    let millis = |value: Option<&str>| value.and_then(|ms| ms.parse::<i64>().ok());
+    let duration = |ms: i64| Duration::from_millis(ms.max(0) as u64);
+    while let Some(arg) = args.next() {
+        match (arg, side_to_move) {
+            ("depth", _) => {
+                if let Some(depth) = args.next().and_then(|d| d.parse::<i8>().ok()) {
+                    limits.depth = Some(depth.clamp(1, MAX_DEPTH));
+                }
+            }
+            ("nodes", _) => limits.nodes = args.next().and_then(|n| n.parse().ok()),
+            ("movetime", _) => limits.movetime = millis(args.next()).map(duration),
+            ("wtime", PieceColor::White) | ("btime", PieceColor::Black) => {
+                if let Some(ms) = millis(args.next()) {
+                    clock.remaining = duration(ms);
+                    has_clock = true;
+                }
+            }
+            ("winc", PieceColor::White) | ("binc", PieceColor::Black) => {
+                clock.increment = millis(args.next()).map(duration).unwrap_or_default();
+            }
+            ("movestogo", _) => clock.moves_to_go = args.next().and_then(|n| n.parse().ok()),
+            // the opponent's clock values
+            ("wtime" | "btime" | "winc" | "binc", _) => {
+                args.next();
+            }
+            ("infinite", _) => infinite = true,
+            _ => {}
+        }
+    }
+
+    if has_clock {
+        limits.clock = Some(clock);
+    }
+    (limits, infinite)
+}
+
+/// Formats the score from the view of the side to move, in centipawns or mate in moves
+fn score_to_uci(score: i32, weights: &Weights) -> String {
+    match mate_distance(score) {
+        Some(plys) => format!("mate {}", (plys + plys.signum()) / 2),
+        None => format!("cp {}", score * 100 / weights.pawn.max(1)),
+    }
+}
+
+/// Transposition table entries fitting into `megabytes`
+fn hash_capacity(megabytes: usize) -> usize {
+    megabytes * 1024 * 1024 / TranspositionTable::ENTRY_SIZE
+}
+
+/// UCI protocol state, writing responses line by line into `output`
+pub struct UciEngine {
+    game: Game,
+    weights: Weights,
+    start_position: String,
+    output: Sender<String>,
+    search: Option<(JoinHandle<()>, StopFlag)>,
+    /// Kept across positions of a game, cleared by `ucinewgame`
+    transposition_table: Arc<TranspositionTable>,
+    threads: usize,
+}
+
+impl UciEngine {
+    pub fn new(output: Sender<String>) -> Self {
+        Self {
+            game: Game::from_fen(STARTING_FEN).expect("starting position is valid"),
+            weights: Weights::default(),
+            start_position: STARTING_FEN.to_string(),
+            output,
+            search: None,
+            transposition_table: Arc::new(TranspositionTable::with_capacity(hash_capacity(
+                DEFAULT_HASH_MB,
+            ))),
+            threads: 1,
+        }
+    }
+
+    fn send(&self, line: impl Into<String>) {
+        // The receiving end only disappears on shutdown
+        let _ = self.output.send(line.into());
+    }
+
+    /// Handles a single command line, returns `false` once the engine should quit
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let mut args = line.split_whitespace();
+        let result = match args.next() {
+            Some("uci") => {
+                self.identify();
+                Ok(())
+            }
+            Some("isready") => {
+                self.send("readyok");
+                Ok(())
+            }
+            Some("ucinewgame") => {
+                self.stop();
+                self.new_game()
+            }
+            Some("setoption") => self.set_option(line),
+            Some("position") => {
+                self.stop();
+                self.set_position(args)
+            }
+            Some("go") => {
+                self.stop();
+                let (limits, infinite) = parse_go(args, self.game.side_to_move());
+                self.go(limits.with_threads(self.threads), infinite);
+                Ok(())
+            }
+            Some("stop") => {
+                self.stop();
+                Ok(())
+            }
+            Some("quit") => {
+                self.stop();
+                return false;
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            self.send(format!("info string {}", err));
+        }
+        true
+    }
+
+    fn identify(&self) {
+        self.send("id name balatro-chess");
+        self.send("id author good-praxis");
+        self.send(format!(
+            "option name {} type string default {}",
+            START_POSITION_OPTION, STARTING_FEN
+        ));
+        self.send(format!(
+            "option name {} type spin default {} min 1 max {}",
+            HASH_OPTION, DEFAULT_HASH_MB, MAX_HASH_MB
+        ));
+        self.send(format!(
+            "option name {} type spin default 1 min 1 max {}",
+            THREADS_OPTION, MAX_THREADS
+        ));
+        self.send(format!(
+            "option name {} type string default <empty>",
+            WEIGHTS_FILE_OPTION
+        ));
+        let defaults = Weights::default();
+        for name in Weights::NAMES {
+            let default = defaults.get(name).unwrap();
+            self.send(format!(
+                "option name {} type spin default {} min -100000 max 100000",
+                name, default
+            ));
+        }
+        self.send("uciok");
+    }
+
+    fn new_game(&mut self) -> Result<(), UciError> {
+        self.transposition_table.clear();
+        self.game = Game::from_fen(&self.start_position)?;
+        Ok(())
+    }
+
+    /// `setoption name <name> value <value>`, names may contain spaces
+    fn set_option(&mut self, line: &str) -> Result<(), UciError> {
+        let rest = line.trim().strip_prefix("setoption").unwrap_or_default();
+        let rest = rest.trim().strip_prefix("name").unwrap_or(rest).trim();
+        let (name, value) = match rest.split_once(" value ") {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => (rest, ""),
+        };
+
+        if name.eq_ignore_ascii_case(START_POSITION_OPTION) {
+            // Validate before accepting the layout
+            Bitboards::from_fen(value)?;
+            self.start_position = value.to_string();
+            return self.new_game();
+        }
+
+        if name.eq_ignore_ascii_case(HASH_OPTION) {
+            let megabytes = value
+                .parse::<usize>()
+                .map_err(|_| UciError::InvalidValue(value.to_string()))?;
+            // Searches still holding the old table finish with it
+            self.transposition_table = Arc::new(TranspositionTable::with_capacity(hash_capacity(
+                megabytes.clamp(1, MAX_HASH_MB),
+            )));
+            return Ok(());
+        }
+
+        if name.eq_ignore_ascii_case(THREADS_OPTION) {
+            let threads = value
+                .parse::<usize>()
+                .map_err(|_| UciError::InvalidValue(value.to_string()))?;
+            self.threads = threads.clamp(1, MAX_THREADS);
+            return Ok(());
+        }
+
+        if name.eq_ignore_ascii_case(WEIGHTS_FILE_OPTION) {
+            if value.is_empty() || value == "<empty>" {
+                return Ok(());
+            }
+            let input = std::fs::read_to_string(value)
+                .map_err(|_| UciError::InvalidValue(value.to_string()))?;
+            self.weights = input.parse()?;
+            return Ok(());
+        }
+
+        let weight = Weights::NAMES
+            .iter()
+            .find(|weight| weight.eq_ignore_ascii_case(name))
+            .ok_or_else(|| UciError::UnknownOption(name.to_string()))?;
+        let value = value
+            .parse()
+            .map_err(|_| UciError::InvalidValue(value.to_string()))?;
+        *self.weights.get_mut(weight).unwrap() = value;
+        Ok(())
+    }
+
+    /// `position [startpos | fen <fen>] [moves <move>...]`
+    fn set_position<'a>(
+        &mut self,
+        mut args: impl Iterator<Item = &'a str>,
+    ) -> Result<(), UciError> {
+        let mut game = match args.next() {
+            Some("fen") => {
+                let fen: Vec<&str> = args.by_ref().take_while(|arg| *arg != "moves").collect();
+                Game::from_fen(&fen.join(" "))?
+            }
+            _ => {
+                // skip the `moves` token
+                args.next();
+                Game::from_fen(&self.start_position)?
+            }
+        };
+
+        for notation in args {
+            let side_to_move = game.side_to_move();
+            let ply = game
+                .boards
+                .ply_from_long_algebraic(side_to_move, notation)
+                .ok_or_else(|| UciError::IllegalMove(notation.to_string()))?;
+            game.play(ply)
+                .map_err(|_| UciError::IllegalMove(notation.to_string()))?;
+        }
+
+        self.game = game;
+        Ok(())
+    }
+
+    /// Starts a search in the background, deepening until a limit or `stop` is reached
+    fn go(&mut self, limits: SearchLimits, infinite: bool) {
+        let stop = limits.stop.clone();
+        let mut boards = self.game.boards.clone();
+        boards.transposition_table = self.transposition_table.clone();
+        let side_to_move = self.game.side_to_move();
+        let weights = self.weights.clone();
+        let output = self.output.clone();
+
+        let search = std::thread::Builder::new().stack_size(SEARCH_STACK_SIZE);
+        let handle = search.spawn(move || {
+            let notation_boards = boards.clone();
+            let (_, pv, _) = boards.search_with_limits(
+                side_to_move,
+                &limits,
+                ClassicalEvaluator::from(weights.clone()),
+                &mut |report| {
+                    let pv_str: Vec<String> = report
+                        .pv
+                        .iter()
+                        .map(|ply| notation_boards.ply_to_long_algebraic(ply))
+                        .collect();
+                    let _ = output.send(format!(
+                        "info depth {} score {} nodes {} time {} pv {}",
+                        report.depth,
+                        score_to_uci(report.score, &weights),
+                        report.nodes,
+                        report.elapsed.as_millis(),
+                        pv_str.join(" ")
+                    ));
+                },
+            );
+
+            // `go infinite` may only answer once it is stopped
+            while infinite && !limits.stop.is_stopped() {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+
+            let bestmove = pv
+                .first()
+                .map(|ply| notation_boards.ply_to_long_algebraic(ply))
+                .unwrap_or("0000".to_string());
+            let _ = output.send(format!("bestmove {}", bestmove));
+        });
+
+        let handle = handle.expect("failed to spawn search thread");
+        self.search = Some((handle, stop));
+    }
+
+    /// Stops a running search, which still reports its best move
+    fn stop(&mut self) {
+        if let Some((handle, stop)) = self.search.take() {
+            stop.stop();
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{Receiver, channel};
+
+    use super::*;
+
+    fn engine() -> (UciEngine, Receiver<String>) {
+        let (sender, receiver) = channel();
+        (UciEngine::new(sender), receiver)
+    }
+
+    #[test]
+    fn uci_handshake() {
+        let (mut engine, output) = engine();
+        assert!(engine.handle_command("uci"));
+        assert!(engine.handle_command("isready"));
+        let lines: Vec<String> = output.try_iter().collect();
+        assert!(lines[0].starts_with("id name"));
+        assert!(lines.iter().any(|line| line.contains("option name queen")));
+        assert!(lines.contains(&"uciok".to_string()));
+        assert_eq!(lines.last().unwrap(), "readyok");
+    }
+
+    #[test]
+    fn quit_ends_loop() {
+        let (mut engine, _output) = engine();
+        assert!(!engine.handle_command("quit"));
+    }
+
+    #[test]
+    fn position_with_moves() {
+        let (mut engine, output) = engine();
+        engine.handle_command("position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(output.try_iter().count(), 0);
+        assert_eq!(
+            engine.game.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+    }
+
+    #[test]
+    fn position_from_fen() {
+        let (mut engine, _output) = engine();
+        engine.handle_command("position fen 7k/8/8/8/8/8/P7/K7 w - - 0 1 moves a2a4");
+        assert_eq!(engine.game.to_fen(), "7k/8/8/8/P7/8/8/K7 b - a3 0 1");
+    }
+
+    #[test]
+    fn position_reports_illegal_move() {
+        let (mut engine, output) = engine();
+        engine.handle_command("position startpos moves e2e5");
+        assert_eq!(output.try_recv().unwrap(), "info string illegal move: e2e5");
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn weights_file_option() {
+        let (mut engine, output) = engine();
+        let path = std::env::temp_dir().join(format!("weights-{}.txt", std::process::id()));
+        let weights = Weights {
+            rook: 120,
+            ..Default::default()
+        };
+        std::fs::write(&path, weights.to_string()).unwrap();
+        engine.handle_command(&format!(
+            "setoption name WeightsFile value {}",
+            path.display()
+        ));
+        assert_eq!(engine.weights, weights);
+
+        std::fs::write(&path, "rook many").unwrap();
+        engine.handle_command(&format!(
+            "setoption name WeightsFile value {}",
+            path.display()
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(engine.weights, weights);
+        assert_eq!(
+            output.try_recv().unwrap(),
+            "info string invalid weights: invalid weight line 'rook many'"
+        );
+    }
+
+    #[test]
+    fn set_weight_option() {
+        let (mut engine, output) = engine();
+        engine.handle_command("setoption name Queen value 200");
+        assert_eq!(engine.weights.queen, 200);
+
+        engine.handle_command("setoption name Queen value lots");
+        engine.handle_command("setoption name Contempt value 10");
+        let lines: Vec<String> = output.try_iter().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "info string invalid value: lots",
+                "info string unknown option: Contempt"
+            ]
+        );
+    }
+
+    #[test]
+    fn start_position_option() {
+        let (mut engine, _output) = engine();
+        let layout = "r4k4/pppppppppp/10/10/PPPPPPPPPP/R4K4 w - - 0 1";
+        engine.handle_command(&format!("setoption name StartPosition value {}", layout));
+        engine.handle_command("position startpos");
+        assert_eq!(engine.game.to_fen(), layout);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn go_depth_reports_info_and_bestmove() {
+        let (mut engine, output) = engine();
+        engine.handle_command("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        engine.handle_command("go depth 2");
+
+        let mut lines = vec![];
+        for line in output.iter() {
+            let done = line.starts_with("bestmove");
+            lines.push(line);
+            if done {
+                break;
+            }
+        }
+        assert!(lines[0].starts_with("info depth 1"));
+        assert!(lines.iter().any(|line| line.contains("score mate 1")));
+        assert!(lines.iter().any(|line| line.contains("pv a1a8")));
+        assert_eq!(lines.last().unwrap(), "bestmove a1a8");
+    }
+
+    #[test]
+    fn go_without_legal_moves() {
+        let (mut engine, output) = engine();
+        engine.handle_command("position fen k7/2Q5/1K6/8/8/8/8/8 b - - 0 1");
+        engine.handle_command("go movetime 100");
+        engine.handle_command("stop");
+        assert_eq!(output.try_iter().last().unwrap(), "bestmove 0000");
+    }
+
+    #[test]
+    fn go_clock_of_side_to_move() {
+        let args = "wtime 1000 btime 60000 winc 10 binc 500 movestogo 20".split_whitespace();
+        let (limits, infinite) = parse_go(args, PieceColor::Black);
+        assert!(!infinite);
+        assert_eq!(
+            limits.clock,
+            Some(Clock {
+                remaining: Duration::from_millis(60_000),
+                increment: Duration::from_millis(500),
+                moves_to_go: Some(20),
+            })
+        );
+
+        let (limits, _) = parse_go("depth 100 nodes 5000".split_whitespace(), PieceColor::White);
+        assert_eq!(limits.depth, Some(MAX_DEPTH));
+        assert_eq!(limits.nodes, Some(5000));
+        assert_eq!(limits.clock, None);
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn go_infinite_until_stop() {
+        let (mut engine, output) = engine();
+        engine.handle_command("go infinite");
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(output.try_iter().all(|line| !line.starts_with("bestmove")));
+        engine.handle_command("stop");
+        assert!(output.try_iter().last().unwrap().starts_with("bestmove"));
+    }
+
+    #[test]
+    fn hash_option_resizes_table() {
+        let (mut engine, output) = engine();
+        engine.handle_command("setoption name Hash value 1");
+        assert_eq!(engine.transposition_table.capacity(), hash_capacity(1));
+        engine.handle_command("setoption name Hash value none");
+        assert_eq!(
+            output.try_recv().unwrap(),
+            "info string invalid value: none"
+        );
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn threads_option() {
+        let (mut engine, output) = engine();
+        engine.handle_command("setoption name Threads value 4");
+        assert_eq!(engine.threads, 4);
+        engine.handle_command("setoption name Threads value 0");
+        assert_eq!(engine.threads, 1);
+        engine.handle_command("setoption name Threads value 2");
+        engine.handle_command("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        engine.handle_command("go depth 2");
+        let bestmove = output
+            .iter()
+            .find(|line| line.starts_with("bestmove"))
+            .unwrap();
+        assert_eq!(bestmove, "bestmove a1a8");
+    }
+
+    #[test]
+    #[cfg(not(miri))]
+    fn table_kept_until_new_game() {
+        let (mut engine, output) = engine();
+        engine.handle_command("position startpos moves e2e4");
+        engine.handle_command("go depth 2");
+        engine.handle_command("stop");
+        assert!(output.try_iter().last().unwrap().starts_with("bestmove"));
+        engine.handle_command("position startpos moves e2e4 e7e5");
+        assert!(!engine.transposition_table.is_empty());
+
+        engine.handle_command("ucinewgame");
+        assert!(engine.transposition_table.is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use super::{
-    bitboard::BitIndex,
-    pieces::{PIECE_COLOR_COUNT, PIECE_TYPE_COUNT, Piece},
+    bitboard::{BitIndex, Bitboard},
+    pieces::{PIECE_COLOR_COUNT, PIECE_TYPE_COUNT, Piece, PieceType},
 };
 use bevy::prelude::Deref;
 use rand::prelude::*;
@@ -9,12 +9,18 @@ use std::ops::BitXorAssign;
 
 pub const PIECE_POSITIONS_COUNT: usize = PIECE_TYPE_COUNT * PIECE_COLOR_COUNT * 256;
 pub const CHANGE_PLAYER_INDEX: usize = PIECE_POSITIONS_COUNT;
-pub const ZOBRIST_TABLE_LENGTH: usize = PIECE_POSITIONS_COUNT + 1;
+pub const EN_PASSANT_INDEX: usize = CHANGE_PLAYER_INDEX + 1;
+pub const UNMOVED_INDEX: usize = EN_PASSANT_INDEX + 256;
+pub const ZOBRIST_TABLE_LENGTH: usize = UNMOVED_INDEX + 256;
 
 #[derive(Debug, Hash, PartialEq, Eq)]
 enum ZobristKey {
     Piece(Piece, u32),
     ChangePlayer,
+    /// Tile a pawn may be captured on en passant
+    EnPassant(u32),
+    /// Tile still holding its initial king or rook, which decides castling rights
+    Unmoved(u32),
 }
 impl ZobristKey {
     #[inline]
@@ -24,15 +30,17 @@ impl ZobristKey {
                 (512 * piece.0 as usize) + (256 * piece.1 as usize) + *position as usize
             }
             Self::ChangePlayer => CHANGE_PLAYER_INDEX,
+            Self::EnPassant(position) => EN_PASSANT_INDEX + *position as usize,
+            Self::Unmoved(position) => UNMOVED_INDEX + *position as usize,
         }
     }
 }
 
 #[derive(Debug, Deref, Default, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct ZobristHash(u32);
+pub struct ZobristHash(u64);
 
-impl From<u32> for ZobristHash {
-    fn from(value: u32) -> Self {
+impl From<u64> for ZobristHash {
+    fn from(value: u64) -> Self {
         Self(value)
     }
 }
@@ -60,7 +68,7 @@ impl Zobrist {
         let mut table = [ZobristHash(0); ZOBRIST_TABLE_LENGTH];
 
         for hash in table.iter_mut().take(ZOBRIST_TABLE_LENGTH) {
-            *hash = rng.random::<u32>().into();
+            *hash = rng.random::<u64>().into();
         }
 
         Self { table }
@@ -90,7 +98,22 @@ impl Zobrist {
         hash
     }
 
-    /// Function works in both directions due to the xoring
+    /// Hash of the tiles set in `castling_pieces` and `en_passant`.
+    /// Passing the xor of two states yields the difference between their hashes.
+    pub fn state_hash(&self, castling_pieces: Bitboard, en_passant: Bitboard) -> ZobristHash {
+        let mut hash = 0.into();
+        for position in set_bits(castling_pieces) {
+            hash ^= self.table[ZobristKey::Unmoved(position).to_index()];
+        }
+        for position in set_bits(en_passant) {
+            hash ^= self.table[ZobristKey::EnPassant(position).to_index()];
+        }
+        hash
+    }
+
+    /// Function works in both directions due to the xoring.
+    /// Promotions are covered by hashing the promoted piece on the landing tile,
+    /// changes of the unmoved pieces and en passant are hashed through `state_hash`
     pub fn update_hash_bitboard(
         &self,
         mut hash: ZobristHash,
@@ -98,18 +121,46 @@ impl Zobrist {
     ) -> ZobristHash {
         // remove previous position for moving piece
         hash ^= self.table[ZobristKey::Piece(ply.moving_piece, *ply.from).to_index()];
-        // add new position for moving piece
-        hash ^= self.table[ZobristKey::Piece(ply.moving_piece, *ply.to).to_index()];
+        // add new position for moving piece, or the piece it promotes into
+        let landing_piece = ply.promoting.unwrap_or(ply.moving_piece);
+        hash ^= self.table[ZobristKey::Piece(landing_piece, *ply.to).to_index()];
         // remove captured piece position
         if let Some(captured) = ply.capturing {
             hash ^= self.table[ZobristKey::Piece(captured.0, *captured.1).to_index()];
         }
+        // move linked piece, i.e. the rook when castling
+        if let Some((other_piece, from, to)) = ply.also_move {
+            hash ^= self.table[ZobristKey::Piece(other_piece, *from).to_index()];
+            hash ^= self.table[ZobristKey::Piece(other_piece, *to).to_index()];
+        }
         // Change player
         hash ^= self.table[ZobristKey::ChangePlayer.to_index()];
 
         hash
     }
 
+    /// Updates a hash of the pawns only, works in both directions like `update_hash_bitboard`.
+    /// Pawns leaving the board by capture or promotion are removed from it
+    pub fn update_pawn_hash(
+        &self,
+        mut hash: ZobristHash,
+        ply: &super::bitboard::Ply,
+    ) -> ZobristHash {
+        if ply.moving_piece.0 == PieceType::Pawn {
+            hash ^= self.table[ZobristKey::Piece(ply.moving_piece, *ply.from).to_index()];
+            if ply.promoting.is_none() {
+                hash ^= self.table[ZobristKey::Piece(ply.moving_piece, *ply.to).to_index()];
+            }
+        }
+        if let Some((captured, idx)) = ply.capturing
+            && captured.0 == PieceType::Pawn
+        {
+            hash ^= self.table[ZobristKey::Piece(captured, *idx).to_index()];
+        }
+
+        hash
+    }
+
     // /// Function works in both directions due to the xoring
     // pub fn update_hash_mailbox(
     //     &self,
@@ -142,11 +193,27 @@ impl Zobrist {
     // }
 }
 
+/// Indices of all set bits
+fn set_bits(board: Bitboard) -> impl Iterator<Item = u32> {
+    let mut remaining = *board;
+    std::iter::from_fn(move || {
+        (remaining != 0).then(|| {
+            let position = remaining.trailing_zeros();
+            remaining &= remaining - 1;
+            position
+        })
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
 
-    use crate::chess_engine::{Game, pieces::WHITE_KNIGHT};
+    use crate::chess_engine::{
+        Game,
+        bitboard::Bitboards,
+        pieces::{BLACK_ROOK, PieceColor, WHITE_KNIGHT, WHITE_PAWN, WHITE_QUEEN},
+    };
 
     use super::*;
 
@@ -205,6 +272,32 @@ mod tests {
         assert_ne!(hash_before, hash_after);
     }
 
+    #[test]
+    fn hash_updates_promotion() {
+        let mut board = Bitboards::new_from_str(
+            r#"
+            0R
+            p0
+            "#,
+        );
+        let ply = crate::chess_engine::bitboard::Ply {
+            moving_piece: WHITE_PAWN,
+            from: 16.into(),
+            to: 1.into(),
+            capturing: Some((BLACK_ROOK, 1.into())),
+            promoting: Some(WHITE_QUEEN),
+            ..Default::default()
+        };
+        board.make_ply(&ply);
+
+        let mut expected = board
+            .zobrist_table
+            .gen_initial_hash_bitboard(board.key_value_pieces_iter());
+        expected ^= board.zobrist_table.table[CHANGE_PLAYER_INDEX];
+
+        assert_eq!(board.zobrist_hash, expected);
+    }
+
     // #[test]
     // fn hash_rewinds_mailbox() {
     //     let mut board = Game::default();
@@ -243,6 +336,79 @@ mod tests {
         let index = ZobristKey::ChangePlayer.to_index();
         assert!(index < ZOBRIST_TABLE_LENGTH);
         assert!(set.insert(index));
+        for i in 0..256 {
+            for key in [ZobristKey::EnPassant(i), ZobristKey::Unmoved(i)] {
+                let index = key.to_index();
+                assert!(index < ZOBRIST_TABLE_LENGTH);
+                assert!(set.insert(index));
+            }
+        }
         assert_eq!(set.len(), ZOBRIST_TABLE_LENGTH);
     }
+
+    #[test]
+    fn hash_distinguishes_en_passant() {
+        let (with, _) = Bitboards::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 3").unwrap();
+        let (without, _) = Bitboards::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 3").unwrap();
+        assert_ne!(with.zobrist_hash, without.zobrist_hash);
+        assert_eq!(with.repetition_count(), 1);
+    }
+
+    #[test]
+    fn hash_distinguishes_castling_rights() {
+        let (with, _) = Bitboards::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let (without, _) = Bitboards::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1").unwrap();
+        assert_ne!(with.zobrist_hash, without.zobrist_hash);
+    }
+
+    #[test]
+    fn hash_distinguishes_promotions() {
+        let (mut boards, _) = Bitboards::from_fen("7k/4P3/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let queen = boards
+            .ply_from_long_algebraic(PieceColor::White, "e7e8q")
+            .unwrap();
+        let knight = boards
+            .ply_from_long_algebraic(PieceColor::White, "e7e8n")
+            .unwrap();
+
+        boards.make_ply(&queen);
+        let queen_hash = boards.zobrist_hash;
+        boards.unmake_ply(&queen);
+        boards.make_ply(&knight);
+        assert_ne!(boards.zobrist_hash, queen_hash);
+    }
+
+    #[test]
+    fn hash_repeats_after_moves_back() {
+        let (mut boards, _) =
+            Bitboards::from_fen(crate::chess_engine::bitboard::STARTING_FEN).unwrap();
+        let start = boards.zobrist_hash;
+        for (color, notation) in [
+            (PieceColor::White, "g1f3"),
+            (PieceColor::Black, "g8f6"),
+            (PieceColor::White, "f3g1"),
+            (PieceColor::Black, "f6g8"),
+        ] {
+            let ply = boards.ply_from_long_algebraic(color, notation).unwrap();
+            boards.make_ply(&ply);
+        }
+        assert_eq!(boards.zobrist_hash, start);
+        assert_eq!(boards.repetition_count(), 2);
+    }
+
+    #[test]
+    fn hash_drops_castling_rights_of_moved_king() {
+        let (mut boards, _) = Bitboards::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        let (without_rights, _) = Bitboards::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 2 2").unwrap();
+        for (color, notation) in [
+            (PieceColor::White, "e1d1"),
+            (PieceColor::Black, "e8d8"),
+            (PieceColor::White, "d1e1"),
+            (PieceColor::Black, "d8e8"),
+        ] {
+            let ply = boards.ply_from_long_algebraic(color, notation).unwrap();
+            boards.make_ply(&ply);
+        }
+        assert_eq!(boards.zobrist_hash, without_rights.zobrist_hash);
+    }
 }
@@ -1,8 +1,6 @@
+use balatro_chess::chess_engine::ChessEnginePlugin;
 use bevy::prelude::*;
 
-mod chess_engine;
-use chess_engine::*;
-
 fn main() {
     let app_window = Some(Window {
         title: "Chess!".to_string(),